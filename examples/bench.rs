@@ -0,0 +1,40 @@
+// Node counts of fixed depth searches over a set of positions, to measure how well the search
+// prunes. Run with `cargo run --release --example bench [depth]`.
+use std::time::Instant;
+
+use chess::{search, Chess, SearchLimits};
+
+const POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+];
+
+fn main() {
+    let depth = match std::env::args().nth(1) {
+        Some(depth) => depth.parse().expect("the depth must be a number"),
+        None => 4,
+    };
+    let start = Instant::now();
+    let mut total = 0;
+    for fen in POSITIONS {
+        let chess = Chess::from_fen(fen).unwrap();
+        let result = search(&chess, SearchLimits::depth(depth));
+        let best_move = result
+            .best_move
+            .map_or("none".to_string(), |mv| mv.to_uci());
+        println!("{:>10} {best_move:<6} {fen}", result.nodes);
+        total += result.nodes;
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{total} nodes in {:.2}s, {:.0} nodes/s",
+        elapsed.as_secs_f64(),
+        total as f64 / elapsed.as_secs_f64()
+    );
+}
@@ -0,0 +1,359 @@
+// Attack lookup tables. Squares use the same layout as the board, 0 is h1 and 63 is a8.
+use std::sync::OnceLock;
+
+use crate::bitboard::Bitboard;
+use crate::chess::Side;
+use crate::square::Square;
+
+// Offsets are (file, rank)
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+const KNIGHT_ATTACKS: [u64; 64] = leaper_attacks(&KNIGHT_OFFSETS);
+const KING_ATTACKS: [u64; 64] = leaper_attacks(&KING_OFFSETS);
+const PAWN_ATTACKS: [[u64; 64]; 2] = [
+    leaper_attacks(&[(-1, -1), (1, -1)]), // Side::Black
+    leaper_attacks(&[(-1, 1), (1, 1)]),   // Side::White
+];
+
+// Magic numbers for this board layout, found with a seeded random search
+const ROOK_MAGICS: [u64; 64] = [
+    0x008000908064C000,
+    0x0040200040001000,
+    0x0180100080A0010A,
+    0x8880041000800800,
+    0x1200100201200804,
+    0x0200020004011008,
+    0x2180010000800600,
+    0x0200005088210204,
+    0x0400800040008021,
+    0x0400400020005000,
+    0x8240801000200080,
+    0x8611001004200900,
+    0x008180800C001800,
+    0x0100800200800400,
+    0x0A02000102000408,
+    0x8020802300104280,
+    0x0080004000402000,
+    0xE010104000402000,
+    0x0800808010002000,
+    0xA280210008100100,
+    0x0001818014000800,
+    0xA002010100080400,
+    0x0080240001020870,
+    0x0001020004048845,
+    0x0081826280004004,
+    0x2020810900284000,
+    0x0200100080802000,
+    0x0200080080100080,
+    0x8083080100100500,
+    0x4406000901000400,
+    0x0005020080800100,
+    0x0090204200008114,
+    0x0010400094800420,
+    0x0900804000802002,
+    0x0201001841002000,
+    0x4100080080801000,
+    0x4540040080800800,
+    0x0002001004040020,
+    0x0281195814001002,
+    0x1240800040800100,
+    0x0880042000524004,
+    0x02C080410206002C,
+    0x0801200241050010,
+    0x8400080010008080,
+    0x0008000500090010,
+    0x0082009084020008,
+    0x4012000108020004,
+    0x9000104D08860004,
+    0x2004204114800100,
+    0x0148802112400300,
+    0x0202842000100880,
+    0x001B080080900080,
+    0x001A002008100600,
+    0x0004008004020080,
+    0x5181000600040300,
+    0x0000044401128A00,
+    0x8044110480002441,
+    0x2008110084402202,
+    0x90806005090010C1,
+    0x000420310A004A42,
+    0x0023001004020801,
+    0x0882001008040102,
+    0x000230088118020C,
+    0x0000019025040042,
+];
+const BISHOP_MAGICS: [u64; 64] = [
+    0x0045010808008680,
+    0x2002080204004898,
+    0x0210009A10400006,
+    0x0824050200810200,
+    0x0006061105004090,
+    0x00010108C0000000,
+    0x0814040282104004,
+    0x0012012201106800,
+    0x10823014100C1040,
+    0x0080C2088802808C,
+    0x0281108410404000,
+    0x0101212041826200,
+    0x0020141028221058,
+    0x2201020202200202,
+    0x000082A801482000,
+    0x0000008401411044,
+    0x0007103014300404,
+    0x0002091110010100,
+    0x42140012040C0808,
+    0x0800808802004020,
+    0x90C4004210140000,
+    0x0800200900A01000,
+    0x00D0400201108810,
+    0x80820183814412A0,
+    0x00A01008202202B4,
+    0x01C2021A09500402,
+    0x0084440208042400,
+    0x800400400C090100,
+    0xBA10040010802100,
+    0xD182009006005000,
+    0x5011021001009004,
+    0x0020420200510400,
+    0x0292104000468800,
+    0x00043009091C0500,
+    0x0280441000020025,
+    0x0042820080080080,
+    0x0440101010010040,
+    0x1000900100808080,
+    0x0108108120089800,
+    0x0044010200012682,
+    0xC002500420900400,
+    0x0040482210710800,
+    0x0002060024000200,
+    0x0281020A44000800,
+    0xA0021200A4000200,
+    0x0001301000840840,
+    0x2868500108444220,
+    0x0004111041000200,
+    0x8044020842080200,
+    0x0000220104210200,
+    0x0000021201044000,
+    0x0000280884040028,
+    0x4012114010858003,
+    0x0000081004082B88,
+    0x3892700508208002,
+    0x00220A041B060400,
+    0x0812020284014881,
+    0x010434A282103100,
+    0x0490400824020800,
+    0x4A20002C00208800,
+    0x000000A011020200,
+    0x4002940A02482202,
+    0x5100100202140406,
+    0x02102000840540C1,
+];
+
+struct Magic {
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: u64) -> usize {
+        self.offset + (((occupied & self.mask).wrapping_mul(self.magic)) >> self.shift) as usize
+    }
+}
+
+struct SliderAttacks {
+    rook_magics: Vec<Magic>,
+    bishop_magics: Vec<Magic>,
+    table: Vec<u64>,
+}
+
+impl SliderAttacks {
+    fn new() -> Self {
+        let mut table = Vec::new();
+        let rook_magics = Self::fill(&mut table, &ROOK_DIRECTIONS, &ROOK_MAGICS);
+        let bishop_magics = Self::fill(&mut table, &BISHOP_DIRECTIONS, &BISHOP_MAGICS);
+        SliderAttacks {
+            rook_magics,
+            bishop_magics,
+            table,
+        }
+    }
+
+    fn fill(table: &mut Vec<u64>, directions: &[(i8, i8)], magics: &[u64; 64]) -> Vec<Magic> {
+        let mut square_magics = Vec::with_capacity(64);
+        for index in 0..64 {
+            let square = Square::new(index).unwrap();
+            let mask = relevant_occupancy(square, directions);
+            let magic = Magic {
+                mask,
+                magic: magics[index as usize],
+                shift: 64 - mask.count_ones(),
+                offset: table.len(),
+            };
+            table.resize(table.len() + (1 << mask.count_ones()), 0);
+
+            // Walk every subset of the mask with the carry-rippler trick
+            let mut occupied: u64 = 0;
+            loop {
+                table[magic.index(occupied)] = sliding_attacks(square, occupied, directions);
+                occupied = occupied.wrapping_sub(mask) & mask;
+                if occupied == 0 {
+                    break;
+                }
+            }
+            square_magics.push(magic);
+        }
+        square_magics
+    }
+}
+
+static SLIDER_ATTACKS: OnceLock<SliderAttacks> = OnceLock::new();
+
+fn slider_attacks() -> &'static SliderAttacks {
+    SLIDER_ATTACKS.get_or_init(SliderAttacks::new)
+}
+
+const fn leaper_attacks(offsets: &[(i8, i8)]) -> [u64; 64] {
+    let mut attacks = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let square = Square::new(index as u8).unwrap();
+        let mut i = 0;
+        while i < offsets.len() {
+            if let Some(target) = square.offset(offsets[i].0, offsets[i].1) {
+                attacks[index] |= target.bit().0;
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    attacks
+}
+
+// Attacks along each ray up to and including the first occupied square
+fn sliding_attacks(square: Square, occupied: u64, directions: &[(i8, i8)]) -> u64 {
+    let mut attacks = 0;
+    for (file_offset, rank_offset) in directions {
+        let mut current = square;
+        while let Some(target) = current.offset(*file_offset, *rank_offset) {
+            attacks |= target.bit().0;
+            if occupied & target.bit().0 != 0 {
+                break;
+            }
+            current = target;
+        }
+    }
+    attacks
+}
+
+// Squares whose occupancy changes the attacks, the last square of each ray never does
+fn relevant_occupancy(square: Square, directions: &[(i8, i8)]) -> u64 {
+    let mut mask = 0;
+    for (file_offset, rank_offset) in directions {
+        let mut current = square;
+        while let Some(target) = current.offset(*file_offset, *rank_offset) {
+            if target.offset(*file_offset, *rank_offset).is_none() {
+                break;
+            }
+            mask |= target.bit().0;
+            current = target;
+        }
+    }
+    mask
+}
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    Bitboard(KNIGHT_ATTACKS[square.index() as usize])
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    Bitboard(KING_ATTACKS[square.index() as usize])
+}
+
+// Squares a pawn of the given side attacks from the square
+pub fn pawn_attacks(side: Side, square: Square) -> Bitboard {
+    Bitboard(PAWN_ATTACKS[side as usize][square.index() as usize])
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let slider_attacks = slider_attacks();
+    let magic = &slider_attacks.rook_magics[square.index() as usize];
+    Bitboard(slider_attacks.table[magic.index(occupied.0)])
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let slider_attacks = slider_attacks();
+    let magic = &slider_attacks.bishop_magics[square.index() as usize];
+    Bitboard(slider_attacks.table[magic.index(occupied.0)])
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magics_match_ray_walking() {
+        let mut state: u64 = 0x9E3779B97F4A7C15;
+        for _ in 0..2000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let occupied = state & state.rotate_left(23);
+            for index in 0..64 {
+                let square = Square::new(index).unwrap();
+                assert_eq!(
+                    rook_attacks(square, Bitboard(occupied)).0,
+                    sliding_attacks(square, occupied, &ROOK_DIRECTIONS)
+                );
+                assert_eq!(
+                    bishop_attacks(square, Bitboard(occupied)).0,
+                    sliding_attacks(square, occupied, &BISHOP_DIRECTIONS)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn leaper_attacks_do_not_wrap() {
+        assert_eq!(
+            knight_attacks(Square::H1),
+            Square::F2.bit() | Square::G3.bit()
+        );
+        assert_eq!(
+            knight_attacks(Square::A8),
+            Square::B6.bit() | Square::C7.bit()
+        );
+        assert_eq!(
+            king_attacks(Square::A1),
+            Square::A2.bit() | Square::B1.bit() | Square::B2.bit()
+        );
+        assert_eq!(pawn_attacks(Side::White, Square::A2), Square::B3.bit());
+        assert_eq!(pawn_attacks(Side::Black, Square::H7), Square::G6.bit());
+    }
+}
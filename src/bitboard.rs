@@ -0,0 +1,260 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+use crate::square::Square;
+
+// A set of squares, bit n is the square with index n so bit 0 is h1 and bit 63 is a8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+    pub const FULL: Bitboard = Bitboard(!0);
+
+    pub const RANK_1: Bitboard = Bitboard(0x0000_0000_0000_00FF);
+    pub const RANK_2: Bitboard = Bitboard(0x0000_0000_0000_FF00);
+    pub const RANK_3: Bitboard = Bitboard(0x0000_0000_00FF_0000);
+    pub const RANK_4: Bitboard = Bitboard(0x0000_0000_FF00_0000);
+    pub const RANK_5: Bitboard = Bitboard(0x0000_00FF_0000_0000);
+    pub const RANK_6: Bitboard = Bitboard(0x0000_FF00_0000_0000);
+    pub const RANK_7: Bitboard = Bitboard(0x00FF_0000_0000_0000);
+    pub const RANK_8: Bitboard = Bitboard(0xFF00_0000_0000_0000);
+
+    pub const FILE_A: Bitboard = Bitboard(0x8080_8080_8080_8080);
+    pub const FILE_B: Bitboard = Bitboard(0x4040_4040_4040_4040);
+    pub const FILE_C: Bitboard = Bitboard(0x2020_2020_2020_2020);
+    pub const FILE_D: Bitboard = Bitboard(0x1010_1010_1010_1010);
+    pub const FILE_E: Bitboard = Bitboard(0x0808_0808_0808_0808);
+    pub const FILE_F: Bitboard = Bitboard(0x0404_0404_0404_0404);
+    pub const FILE_G: Bitboard = Bitboard(0x0202_0202_0202_0202);
+    pub const FILE_H: Bitboard = Bitboard(0x0101_0101_0101_0101);
+
+    pub const RANKS: [Bitboard; 8] = [
+        Bitboard::RANK_1,
+        Bitboard::RANK_2,
+        Bitboard::RANK_3,
+        Bitboard::RANK_4,
+        Bitboard::RANK_5,
+        Bitboard::RANK_6,
+        Bitboard::RANK_7,
+        Bitboard::RANK_8,
+    ];
+    pub const FILES: [Bitboard; 8] = [
+        Bitboard::FILE_A,
+        Bitboard::FILE_B,
+        Bitboard::FILE_C,
+        Bitboard::FILE_D,
+        Bitboard::FILE_E,
+        Bitboard::FILE_F,
+        Bitboard::FILE_G,
+        Bitboard::FILE_H,
+    ];
+
+    // The two long diagonals, a1-h8 and h1-a8
+    pub const DIAGONAL_A1_H8: Bitboard = Bitboard(0x0102_0408_1020_4080);
+    pub const DIAGONAL_H1_A8: Bitboard = Bitboard(0x8040_2010_0804_0201);
+
+    pub const DARK_SQUARES: Bitboard = Bitboard(0x55AA_55AA_55AA_55AA);
+    pub const LIGHT_SQUARES: Bitboard = Bitboard(!0x55AA_55AA_55AA_55AA);
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, square: Square) -> bool {
+        self.0 & square.bit().0 != 0
+    }
+
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    // Lowest square of the set, the one pop_lsb and iter return first
+    pub fn lsb(self) -> Option<Square> {
+        Square::new(self.0.trailing_zeros() as u8)
+    }
+
+    // Removes the lowest square from the set and returns it
+    pub fn pop_lsb(&mut self) -> Option<Square> {
+        let square = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
+    pub fn iter(self) -> BitboardIter {
+        BitboardIter(self)
+    }
+
+    // Shifts move every square one step and drop the ones that would leave the board, so
+    // nothing wraps around from the h file to the a file or the other way
+    pub fn north(self) -> Bitboard {
+        Bitboard(self.0 << 8)
+    }
+
+    pub fn south(self) -> Bitboard {
+        Bitboard(self.0 >> 8)
+    }
+
+    pub fn east(self) -> Bitboard {
+        Bitboard((self.0 & !Bitboard::FILE_H.0) >> 1)
+    }
+
+    pub fn west(self) -> Bitboard {
+        Bitboard((self.0 & !Bitboard::FILE_A.0) << 1)
+    }
+
+    pub fn north_east(self) -> Bitboard {
+        self.east().north()
+    }
+
+    pub fn north_west(self) -> Bitboard {
+        self.west().north()
+    }
+
+    pub fn south_east(self) -> Bitboard {
+        self.east().south()
+    }
+
+    pub fn south_west(self) -> Bitboard {
+        self.west().south()
+    }
+}
+
+impl From<Square> for Bitboard {
+    fn from(square: Square) -> Self {
+        square.bit()
+    }
+}
+
+pub struct BitboardIter(Bitboard);
+
+impl Iterator for BitboardIter {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        self.0.pop_lsb()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count() as usize;
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for BitboardIter {}
+
+impl IntoIterator for Bitboard {
+    type Item = Square;
+    type IntoIter = BitboardIter;
+
+    fn into_iter(self) -> BitboardIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<Square> for Bitboard {
+    fn from_iter<I: IntoIterator<Item = Square>>(squares: I) -> Self {
+        squares
+            .into_iter()
+            .fold(Bitboard::EMPTY, |bitboard, square| bitboard | square.bit())
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+
+    fn bitand(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 & other.0)
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+
+    fn bitor(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 | other.0)
+    }
+}
+
+impl BitXor for Bitboard {
+    type Output = Bitboard;
+
+    fn bitxor(self, other: Bitboard) -> Bitboard {
+        Bitboard(self.0 ^ other.0)
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0)
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, other: Bitboard) {
+        self.0 &= other.0;
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, other: Bitboard) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, other: Bitboard) {
+        self.0 ^= other.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_sets_match_squares() {
+        for (rank, bitboard) in Bitboard::RANKS.into_iter().enumerate() {
+            assert!(bitboard.iter().all(|square| square.rank() as usize == rank));
+            assert_eq!(bitboard.count(), 8);
+        }
+        for (file, bitboard) in Bitboard::FILES.into_iter().enumerate() {
+            assert!(bitboard.iter().all(|square| square.file() as usize == file));
+            assert_eq!(bitboard.count(), 8);
+        }
+        assert!(Bitboard::DIAGONAL_A1_H8
+            .iter()
+            .all(|square| square.file() == square.rank()));
+        assert!(Bitboard::DIAGONAL_H1_A8
+            .iter()
+            .all(|square| square.file() + square.rank() == 7));
+        assert!(Bitboard::DARK_SQUARES.iter().all(Square::is_dark));
+        assert_eq!(Bitboard::DARK_SQUARES.count(), 32);
+    }
+
+    #[test]
+    fn shifts_do_not_wrap() {
+        assert_eq!(Bitboard::FILE_H.east(), Bitboard::EMPTY);
+        assert_eq!(Bitboard::FILE_A.west(), Bitboard::EMPTY);
+        assert_eq!(Bitboard::RANK_8.north(), Bitboard::EMPTY);
+        assert_eq!(Bitboard::RANK_1.south(), Bitboard::EMPTY);
+        assert_eq!(Square::E4.bit().north_west(), Square::D5.bit());
+        assert_eq!(Square::H4.bit().south_east(), Bitboard::EMPTY);
+        assert_eq!(Bitboard::FILE_A.east(), Bitboard::FILE_B);
+    }
+
+    #[test]
+    fn iterates_in_square_order() {
+        let mut bitboard: Bitboard = [Square::A8, Square::E4, Square::H1].into_iter().collect();
+        assert!(bitboard.contains(Square::E4));
+        assert!(!bitboard.contains(Square::E5));
+        assert_eq!(
+            bitboard.iter().collect::<Vec<_>>(),
+            [Square::H1, Square::E4, Square::A8]
+        );
+        assert_eq!(bitboard.pop_lsb(), Some(Square::H1));
+        assert_eq!(bitboard.count(), 2);
+        assert_eq!(Bitboard::EMPTY.iter().next(), None);
+    }
+}
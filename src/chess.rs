@@ -1,601 +1,1167 @@
-const CLEAR_RANK: [u64; 8] = [
-    18446744073709551360,
-    18446744073709486335,
-    18446744073692839935,
-    18446744069431361535,
-    18446742978492891135,
-    18446463698244468735,
-    18374967954648334335,
-    72057594037927935,
-];
-
-const CLEAR_FILE: [u64; 8] = [
-    9187201950435737471,
-    13816973012072644543,
-    16131858542891098079,
-    17289301308300324847,
-    17868022691004938231,
-    18157383382357244923,
-    18302063728033398269,
-    18374403900871474942,
-];
-
-pub struct Side;
+use crate::attacks;
+use crate::bitboard::Bitboard;
+use crate::square::Square;
+use crate::zobrist;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Black = 0,
+    White = 1,
+}
+
 impl Side {
-    pub const WHITE: usize = 1;
-    pub const BLACK: usize = 0;
+    pub fn get_opposite(self) -> Side {
+        match self {
+            Side::White => Side::Black,
+            Side::Black => Side::White,
+        }
+    }
+}
 
-    pub fn get_opposite(side: usize) -> usize {
-        if side == Side::WHITE {
-            Side::BLACK
-        } else {
-            Side::WHITE
+pub struct Castling;
+impl Castling {
+    pub const WHITE_KING_SIDE: u8 = 1;
+    pub const WHITE_QUEEN_SIDE: u8 = 2;
+    pub const BLACK_KING_SIDE: u8 = 4;
+    pub const BLACK_QUEEN_SIDE: u8 = 8;
+    pub const ALL: u8 = 15;
+
+    pub fn get_rights(side: Side) -> (u8, u8) {
+        match side {
+            Side::White => (Castling::WHITE_KING_SIDE, Castling::WHITE_QUEEN_SIDE),
+            Side::Black => (Castling::BLACK_KING_SIDE, Castling::BLACK_QUEEN_SIDE),
         }
     }
 }
 
-pub struct PieceType;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceType {
+    Pawn = 0,
+    Bishop = 1,
+    Knight = 2,
+    Rook = 3,
+    Queen = 4,
+    King = 5,
+}
+
 impl PieceType {
-    pub const PAWN: usize = 0;
-    pub const BISHOP: usize = 1;
-    pub const KNIGHT: usize = 2;
-    pub const ROOK: usize = 3;
-    pub const QUEEN: usize = 4;
-    pub const KING: usize = 5;
+    // In board index order
+    pub const ALL: [PieceType; 6] = [
+        PieceType::Pawn,
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ];
+
+    pub const PROMOTIONS: [PieceType; 4] = [
+        PieceType::Queen,
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Knight,
+    ];
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Piece {
-    pub piece_type: usize,
-    pub side: usize,
+    pub piece_type: PieceType,
+    pub side: Side,
 }
 
 impl Piece {
-    pub fn new(side: usize, piece_type: usize) -> Self {
+    pub fn new(side: Side, piece_type: PieceType) -> Self {
         Piece { piece_type, side }
     }
 
     pub fn get_char(&self) -> char {
-        let mut character = match self.piece_type {
-            PieceType::PAWN => 'p',
-            PieceType::KNIGHT => 'n',
-            PieceType::BISHOP => 'b',
-            PieceType::ROOK => 'r',
-            PieceType::QUEEN => 'q',
-            PieceType::KING => 'k',
-            _ => '-',
+        let character = match self.piece_type {
+            PieceType::Pawn => 'p',
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Rook => 'r',
+            PieceType::Queen => 'q',
+            PieceType::King => 'k',
         };
-        if self.side == Side::WHITE {
-            character = character.to_ascii_uppercase();
+        match self.side {
+            Side::White => character.to_ascii_uppercase(),
+            Side::Black => character,
         }
-        character
+    }
+
+    pub fn from_char(character: char) -> Option<Self> {
+        let piece_type = match character.to_ascii_lowercase() {
+            'p' => PieceType::Pawn,
+            'n' => PieceType::Knight,
+            'b' => PieceType::Bishop,
+            'r' => PieceType::Rook,
+            'q' => PieceType::Queen,
+            'k' => PieceType::King,
+            _ => return None,
+        };
+        let side = if character.is_ascii_uppercase() {
+            Side::White
+        } else {
+            Side::Black
+        };
+        Some(Piece::new(side, piece_type))
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct EnPassant(u8, u8); // EnPassant(piece_pos, target_square_pos)
+pub struct MoveFlag;
+impl MoveFlag {
+    pub const QUIET: u8 = 0;
+    pub const CAPTURE: u8 = 1;
+    pub const DOUBLE_PUSH: u8 = 2;
+    pub const EN_PASSANT: u8 = 4;
+    pub const CASTLE: u8 = 8;
+}
 
-pub fn get_nth_bit(num: u64, n: u8) -> u8 {
-    return ((num >> (n)) & 1) as u8;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<PieceType>,
+    pub flags: u8,
 }
 
-#[derive(Clone, Copy)]
-pub struct Chess {
-    pub board: [[u64; 6]; 2],
-    pub turn: usize,
-    pub en_passant: Option<EnPassant>,
+impl Move {
+    pub fn new(from: Square, to: Square, promotion: Option<PieceType>, flags: u8) -> Self {
+        Move {
+            from,
+            to,
+            promotion,
+            flags,
+        }
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.flags & MoveFlag::CAPTURE != 0
+    }
+
+    pub fn is_double_push(&self) -> bool {
+        self.flags & MoveFlag::DOUBLE_PUSH != 0
+    }
+
+    pub fn is_en_passant(&self) -> bool {
+        self.flags & MoveFlag::EN_PASSANT != 0
+    }
+
+    pub fn is_castle(&self) -> bool {
+        self.flags & MoveFlag::CASTLE != 0
+    }
 }
 
-impl Chess {
-    pub fn new() -> Self {
-        let mut board: [[u64; 6]; 2] = [[0; 6]; 2];
-        board[Side::WHITE][PieceType::PAWN] = 65280;
-        // board[Side::WHITE][PieceType::PAWN] = 1099511627776;
-        board[Side::WHITE][PieceType::BISHOP] = 36;
-        board[Side::WHITE][PieceType::KNIGHT] = 66;
-        board[Side::WHITE][PieceType::ROOK] = 129;
-        board[Side::WHITE][PieceType::QUEEN] = 16;
-        board[Side::WHITE][PieceType::KING] = 8;
-
-        board[Side::BLACK][PieceType::PAWN] = 71776119061217280;
-        board[Side::BLACK][PieceType::BISHOP] = 2594073385365405696;
-        board[Side::BLACK][PieceType::KNIGHT] = 4755801206503243776;
-        board[Side::BLACK][PieceType::ROOK] = 9295429630892703744;
-        board[Side::BLACK][PieceType::QUEEN] = 1152921504606846976;
-        board[Side::BLACK][PieceType::KING] = 576460752303423488;
-        // board[Side::BLACK][PieceType::KING] = 4294967296;
-
-        Self {
-            board,
-            turn: Side::WHITE,
-            en_passant: None,
-        }
-    }
-
-    pub fn get_piece_at(self, i: u8) -> Option<Piece> {
-        for (_i, side) in self.board.iter().enumerate() {
-            for (j, piece) in side.iter().enumerate() {
-                if (piece >> i) & 1 == 1 {
-                    return Some(Piece::new(_i, j));
-                }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromotionError {
+    MissingPiece,
+    InvalidPiece,
+    NotAPromotion,
+}
+
+impl std::fmt::Display for PromotionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PromotionError::MissingPiece => write!(f, "a pawn reaching the last rank must promote"),
+            PromotionError::InvalidPiece => {
+                write!(
+                    f,
+                    "pawns can only promote to a queen, rook, bishop or knight"
+                )
             }
+            PromotionError::NotAPromotion => write!(f, "this move is not a promotion"),
         }
-        None
     }
+}
 
-    pub fn legal_moves(&mut self, i: u8, turn: Option<usize>) -> u64 {
-        let piece = self.get_piece_at(i);
-        if turn.is_some() {
-            if piece.is_some() {
-                if turn.unwrap() != piece.unwrap().side {
-                    return 0;
-                }
+impl std::error::Error for PromotionError {}
+
+// Why try_move refused a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    NoPiece(Square),
+    NotYourTurn(Square),
+    LeavesKingInCheck(Square, Square), // Also castling out of or through check
+    UnreachableSquare(Square, Square),
+    InvalidPromotion(PromotionError),
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MoveError::NoPiece(square) => write!(f, "there is no piece on {square}"),
+            MoveError::NotYourTurn(square) => {
+                write!(f, "the piece on {square} belongs to the side not to move")
             }
-        }
-        match piece {
-            Some(piece) => {
-                if piece.piece_type == PieceType::KING {
-                    self.get_king_pseudo_moves(Some(i), piece.side)
-                } else if piece.piece_type == PieceType::KNIGHT {
-                    self.get_knight_pseudo_moves(Some(i), piece.side)
-                } else if piece.piece_type == PieceType::BISHOP {
-                    self.get_bishop_pseudo_moves(Some(i), piece.side)
-                } else if piece.piece_type == PieceType::ROOK {
-                    self.get_rook_pseudo_moves(Some(i), piece.side)
-                } else if piece.piece_type == PieceType::QUEEN {
-                    self.get_queen_pseudo_moves(Some(i), piece.side)
-                } else if piece.piece_type == PieceType::PAWN && piece.side == Side::WHITE {
-                    self.get_white_pawn_moves(Some(i))
-                } else if piece.piece_type == PieceType::PAWN && piece.side == Side::BLACK {
-                    self.get_black_pawn_moves(Some(i))
-                } else {
-                    0
-                }
+            MoveError::LeavesKingInCheck(from, to) => {
+                write!(
+                    f,
+                    "moving from {from} to {to} would leave the king in check"
+                )
+            }
+            MoveError::UnreachableSquare(from, to) => {
+                write!(f, "the piece on {from} can't reach {to}")
             }
-            _ => 0,
+            MoveError::InvalidPromotion(err) => write!(f, "{err}"),
         }
     }
+}
 
-    fn get_king_pseudo_moves(&mut self, piece_pos: Option<u8>, side: usize) -> u64 {
-        let king_pos: u64;
-        let mut own_side: u64 = 0;
-        for pieces in self.board[side] {
-            own_side = own_side | pieces;
-        }
-
-        match piece_pos {
-            Some(n) => king_pos = 1 << n,
-            None => king_pos = self.board[side][PieceType::KING],
-        }
+impl std::error::Error for MoveError {}
+
+// What happened when try_move played a move
+#[derive(Debug, Clone, Copy)]
+pub struct MoveOutcome {
+    pub mv: Move,
+    pub undo: UndoInfo, // Pass to unmake_move to take the move back
+    pub captured: Option<PieceType>,
+    pub promotion: Option<PieceType>,
+    pub is_check: bool,
+    pub status: GameStatus, // Anything but Ongoing ends the game
+}
 
-        let king_clip1 = king_pos & CLEAR_FILE[0];
-        let king_clip2 = king_pos & CLEAR_FILE[7];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate(Side), // Checkmate(winner)
+    Stalemate,
+    Draw(DrawReason),
+}
 
-        let s1 = king_clip2 << 7;
-        let s2 = king_pos << 8;
-        let s3 = king_clip1 << 9;
-        let s4 = king_clip1 << 1;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    FiftyMoveRule,       // Claimable
+    SeventyFiveMoveRule, // Automatic
+    ThreefoldRepetition, // Claimable
+    FivefoldRepetition,  // Automatic
+    InsufficientMaterial,
+}
 
-        let s5 = king_clip1 >> 7;
-        let s6 = king_pos >> 8;
-        let s7 = king_clip2 >> 9;
-        let s8 = king_clip2 >> 1;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    InvalidRank(String),
+    InvalidPiece(char),
+    InvalidKings,
+    PawnOnBackRank,
+    OpponentInCheck,
+    InvalidSide(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
 
-        let king_moves = s1 | s2 | s3 | s4 | s5 | s6 | s7 | s8;
-        let valid_moves = king_moves & !own_side;
-        if piece_pos.is_none() {
-            valid_moves
-        } else {
-            self.get_legal_moves(king_pos, valid_moves)
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FenError::WrongFieldCount(n) => write!(f, "expected 4 to 6 fields, found {n}"),
+            FenError::WrongRankCount(n) => write!(f, "expected 8 ranks, found {n}"),
+            FenError::InvalidRank(rank) => write!(f, "rank '{rank}' does not have 8 squares"),
+            FenError::InvalidPiece(c) => write!(f, "'{c}' is not a piece"),
+            FenError::InvalidKings => write!(f, "each side must have exactly one king"),
+            FenError::PawnOnBackRank => write!(f, "pawns can't be on the first or last rank"),
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            FenError::InvalidSide(side) => write!(f, "side to move '{side}' is not 'w' or 'b'"),
+            FenError::InvalidCastling(castling) => {
+                write!(f, "invalid castling rights '{castling}'")
+            }
+            FenError::InvalidEnPassant(square) => {
+                write!(f, "invalid en passant square '{square}'")
+            }
+            FenError::InvalidHalfmoveClock(clock) => {
+                write!(f, "invalid halfmove clock '{clock}'")
+            }
+            FenError::InvalidFullmoveNumber(number) => {
+                write!(f, "invalid fullmove number '{number}'")
+            }
         }
     }
+}
 
-    fn get_knight_pseudo_moves(&mut self, piece_pos: Option<u8>, side: usize) -> u64 {
-        let knights_pos;
-        let mut own_side = 0;
-        for pieces in self.board[side] {
-            own_side = own_side | pieces;
-        }
-        match piece_pos {
-            Some(n) => knights_pos = 1 << n,
-            None => knights_pos = self.board[side][PieceType::KNIGHT],
-        }
+impl std::error::Error for FenError {}
 
-        let s1_clip = CLEAR_FILE[7] & CLEAR_FILE[6];
-        let s2_clip = CLEAR_FILE[7];
-        let s3_clip = CLEAR_FILE[0];
-        let s4_clip = CLEAR_FILE[0] & CLEAR_FILE[1];
+#[derive(Debug, Clone, Copy)]
+pub struct EnPassant(pub Square, pub Square); // EnPassant(piece_pos, target_square_pos)
 
-        let s5_clip = CLEAR_FILE[0] & CLEAR_FILE[1];
-        let s6_clip = CLEAR_FILE[0];
-        let s7_clip = CLEAR_FILE[7];
-        let s8_clip = CLEAR_FILE[7] & CLEAR_FILE[6];
+// State that a move destroys and unmake_move needs to restore
+#[derive(Debug, Clone, Copy)]
+pub struct UndoInfo {
+    captured: Option<PieceType>,
+    castling: u8,
+    en_passant: Option<EnPassant>,
+    halfmove_clock: u32,
+    hash: u64,
+}
 
-        let s1 = (knights_pos & s1_clip) << 6;
-        let s2 = (knights_pos & s2_clip) << 15;
-        let s3 = (knights_pos & s3_clip) << 17;
-        let s4 = (knights_pos & s4_clip) << 10;
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
-        let s5 = (knights_pos & s5_clip) >> 6;
-        let s6 = (knights_pos & s6_clip) >> 15;
-        let s7 = (knights_pos & s7_clip) >> 17;
-        let s8 = (knights_pos & s8_clip) >> 10;
+#[derive(Clone)]
+pub struct Chess {
+    board: [[Bitboard; 6]; 2],
+    // The same pieces by square, and the occupancy of each side, kept in sync with the board by
+    // put_piece and remove_piece
+    mailbox: [Option<Piece>; 64],
+    occupancy: [Bitboard; 2],
+    occupied: Bitboard,
+    pub turn: Side,
+    pub en_passant: Option<EnPassant>,
+    pub castling: u8,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    hash: u64,
+    // Hashes of every position so far, for repetitions
+    history: Vec<u64>,
+}
 
-        let knight_valid = s1 | s2 | s3 | s4 | s5 | s6 | s7 | s8;
-        let valid_moves = knight_valid & !own_side;
-        if piece_pos.is_none() {
-            valid_moves
-        } else {
-            self.get_legal_moves(knights_pos, valid_moves)
-        }
+impl Chess {
+    pub fn new() -> Self {
+        Self::from_fen(STARTING_FEN).unwrap()
     }
 
-    fn get_bishop_pseudo_moves(&mut self, piece_pos: Option<u8>, side: usize) -> u64 {
-        let bitboard: u64;
-        let mut own_side: u64 = 0;
-        let mut opp_side: u64 = 0;
-        for (i, pieces) in self.board[side].iter().enumerate() {
-            own_side = own_side | pieces;
-            opp_side = opp_side | self.board[Side::get_opposite(side)][i];
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if !(4..=6).contains(&fields.len()) {
+            return Err(FenError::WrongFieldCount(fields.len()));
         }
 
-        match piece_pos {
-            Some(n) => bitboard = 1 << n,
-            None => bitboard = self.board[side][PieceType::BISHOP],
+        // Piece placement, ranks go from 8 down to 1 and files from a to h
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
+        let mut board = [[Bitboard::EMPTY; 6]; 2];
+        for (rank_index, rank) in ranks.iter().enumerate() {
+            let mut file = 0;
+            let mut after_digit = false;
+            for character in rank.chars() {
+                if let Some(empty) = character.to_digit(10) {
+                    // Runs of empty squares are a single digit, 1 to 8
+                    if after_digit || !(1..=8).contains(&empty) {
+                        return Err(FenError::InvalidRank(rank.to_string()));
+                    }
+                    file += empty;
+                    after_digit = true;
+                } else {
+                    let piece =
+                        Piece::from_char(character).ok_or(FenError::InvalidPiece(character))?;
+                    if file >= 8 {
+                        return Err(FenError::InvalidRank(rank.to_string()));
+                    }
+                    let square = Square::from_file_rank(file as u8, 7 - rank_index as u8).unwrap();
+                    board[piece.side as usize][piece.piece_type as usize] |= square.bit();
+                    file += 1;
+                    after_digit = false;
+                }
+            }
+            if file != 8 {
+                return Err(FenError::InvalidRank(rank.to_string()));
+            }
+        }
+        let kings = |side: Side| board[side as usize][PieceType::King as usize];
+        if kings(Side::White).count() != 1 || kings(Side::Black).count() != 1 {
+            return Err(FenError::InvalidKings);
+        }
+        let pawns = board[Side::White as usize][PieceType::Pawn as usize]
+            | board[Side::Black as usize][PieceType::Pawn as usize];
+        if !(pawns & (Bitboard::RANK_1 | Bitboard::RANK_8)).is_empty() {
+            return Err(FenError::PawnOnBackRank);
         }
 
-        let mut valid_moves = 0;
+        let turn = match fields[1] {
+            "w" => Side::White,
+            "b" => Side::Black,
+            side => return Err(FenError::InvalidSide(side.to_string())),
+        };
 
-        for n in 0..=63 {
-            if get_nth_bit(bitboard, n as u8) == 1 {
-                let pos = (2 as u64).pow(n);
-                let get_spot = |i: usize, j: usize| {
-                    if i == 0 && pos & CLEAR_FILE[7] != 0 {
-                        pos << 7 * j // top right
-                    } else if i == 1 && pos & CLEAR_FILE[7] != 0 {
-                        pos >> 9 * j // bottom right
-                    } else if i == 2 && pos & CLEAR_FILE[0] != 0 {
-                        pos >> 7 * j // bottom left
-                    } else if i == 3 && pos & CLEAR_FILE[0] != 0 {
-                        pos << 9 * j // top left
-                    } else {
-                        0
-                    }
+        let mut castling = 0;
+        if fields[2] != "-" {
+            for character in fields[2].chars() {
+                let right = match character {
+                    'K' => Castling::WHITE_KING_SIDE,
+                    'Q' => Castling::WHITE_QUEEN_SIDE,
+                    'k' => Castling::BLACK_KING_SIDE,
+                    'q' => Castling::BLACK_QUEEN_SIDE,
+                    _ => return Err(FenError::InvalidCastling(fields[2].to_string())),
                 };
+                if castling & right != 0 {
+                    return Err(FenError::InvalidCastling(fields[2].to_string()));
+                }
+                castling |= right;
+            }
+        }
 
-                for i in 0..=3 {
-                    'inner: for j in 1..=7 {
-                        let spot = get_spot(i, j);
-                        if spot & own_side != 0 {
-                            // There is a piece of same side on the way bllocking
-                            break 'inner;
-                        }
+        // The target square is behind the pawn that just made a double push
+        let en_passant = match (fields[3], fields[3].parse::<Square>()) {
+            ("-", _) => None,
+            (_, Ok(target)) if turn == Side::White && target.rank() == 5 => {
+                Some(EnPassant(target.offset(0, -1).unwrap(), target))
+            }
+            (_, Ok(target)) if turn == Side::Black && target.rank() == 2 => {
+                Some(EnPassant(target.offset(0, 1).unwrap(), target))
+            }
+            (square, _) => return Err(FenError::InvalidEnPassant(square.to_string())),
+        };
 
-                        valid_moves = valid_moves | spot;
+        let halfmove_clock = match fields.get(4) {
+            Some(clock) => clock
+                .parse()
+                .map_err(|_| FenError::InvalidHalfmoveClock(clock.to_string()))?,
+            None => 0,
+        };
+        let fullmove_number = match fields.get(5) {
+            Some(number) => match number.parse() {
+                Ok(number) if number > 0 => number,
+                _ => return Err(FenError::InvalidFullmoveNumber(number.to_string())),
+            },
+            None => 1,
+        };
 
-                        if (i <= 1 && spot & CLEAR_FILE[7] == 0) // checking overflow
-                    || (i > 1 && spot & CLEAR_FILE[0] == 0) // checking overflow
-                    || spot & opp_side != 0
-                        {
-                            // checking if there is an opponent piece to capture
-                            break 'inner;
-                        }
-                    }
+        let mut chess = Self {
+            board: [[Bitboard::EMPTY; 6]; 2],
+            mailbox: [None; 64],
+            occupancy: [Bitboard::EMPTY; 2],
+            occupied: Bitboard::EMPTY,
+            turn,
+            en_passant,
+            castling,
+            halfmove_clock,
+            fullmove_number,
+            hash: 0,
+            history: Vec::new(),
+        };
+        for side in [Side::Black, Side::White] {
+            for piece_type in PieceType::ALL {
+                for square in board[side as usize][piece_type as usize] {
+                    chess.put_piece(Piece::new(side, piece_type), square);
                 }
             }
         }
+        // Every castling right needs the king and that rook on their starting squares
+        for (side, right, rook) in [
+            (Side::White, Castling::WHITE_KING_SIDE, Square::H1),
+            (Side::White, Castling::WHITE_QUEEN_SIDE, Square::A1),
+            (Side::Black, Castling::BLACK_KING_SIDE, Square::H8),
+            (Side::Black, Castling::BLACK_QUEEN_SIDE, Square::A8),
+        ] {
+            let king = match side {
+                Side::White => Square::E1,
+                Side::Black => Square::E8,
+            };
+            if castling & right != 0
+                && (!chess.get_pieces(side, PieceType::King).contains(king)
+                    || !chess.get_pieces(side, PieceType::Rook).contains(rook))
+            {
+                return Err(FenError::InvalidCastling(fields[2].to_string()));
+            }
+        }
 
-        if piece_pos.is_none() {
-            valid_moves
-        } else {
-            self.get_legal_moves(bitboard, valid_moves)
+        // The pawn has to be there, and the squares it passed over empty
+        if let Some(EnPassant(pawn, target)) = en_passant {
+            let start = Square::from_file_rank(target.file(), 2 * target.rank() - pawn.rank());
+            let occupied = chess.get_occupied();
+            if !chess
+                .get_pieces(turn.get_opposite(), PieceType::Pawn)
+                .contains(pawn)
+                || occupied.contains(target)
+                || start.is_some_and(|start| occupied.contains(start))
+            {
+                return Err(FenError::InvalidEnPassant(fields[3].to_string()));
+            }
+        }
+        // The side to move could take the king
+        if chess.is_in_check(turn.get_opposite()) {
+            return Err(FenError::OpponentInCheck);
         }
+        chess.hash = chess.compute_hash();
+        chess.history.push(chess.hash);
+        Ok(chess)
     }
 
-    fn get_rook_pseudo_moves(&mut self, piece_pos: Option<u8>, side: usize) -> u64 {
-        let bitboard: u64;
-        let mut own_side: u64 = 0;
-        let mut opp_side: u64 = 0;
-        for (i, pieces) in self.board[side].iter().enumerate() {
-            own_side = own_side | pieces;
-            opp_side = opp_side | self.board[Side::get_opposite(side)][i];
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.get_piece_at(Square::from_file_rank(file, rank).unwrap()) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        placement.push(piece.get_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                placement.push('/');
+            }
         }
 
-        match piece_pos {
-            Some(n) => bitboard = 1 << n,
-            None => bitboard = self.board[side][PieceType::ROOK],
+        let turn = match self.turn {
+            Side::White => "w",
+            Side::Black => "b",
         };
 
-        let mut valid_moves = 0;
-
-        for n in 0..=63 {
-            if get_nth_bit(bitboard, n as u8) == 1 {
-                let pos = (2 as u64).pow(n);
-                let get_spot = |i: usize, j: usize| {
-                    if i == 0 && pos & CLEAR_FILE[7] != 0 {
-                        pos >> 1 * j // Rook right
-                    } else if i == 1 && pos & CLEAR_FILE[0] != 0 {
-                        pos << 1 * j // Rook left
-                    } else if i == 2 {
-                        pos << 8 * j // Rook up
-                    } else if i == 3 {
-                        pos >> 8 * j // Rook down
-                    } else {
-                        0
-                    }
-                };
-                for i in 0..=3 {
-                    for j in 1..=7 {
-                        let spot = get_spot(i, j);
-                        if spot & own_side != 0 {
-                            // There is a piece of same side on the way bllocking
-                            break;
-                        }
-                        valid_moves = valid_moves | spot;
-
-                        if (i == 0 && spot & CLEAR_FILE[7] == 0) // checking overflow
-                    || (i == 1 && spot & CLEAR_FILE[0] == 0) // checking overflow
-                    || spot & opp_side != 0
-                        // checking if there is an opponent piece to capture
-                        {
-                            break;
-                        }
-                    }
-                }
+        let mut castling = String::new();
+        for (right, character) in [
+            (Castling::WHITE_KING_SIDE, 'K'),
+            (Castling::WHITE_QUEEN_SIDE, 'Q'),
+            (Castling::BLACK_KING_SIDE, 'k'),
+            (Castling::BLACK_QUEEN_SIDE, 'q'),
+        ] {
+            if self.castling & right != 0 {
+                castling.push(character);
             }
         }
-        if piece_pos.is_none() {
-            valid_moves
-        } else {
-            self.get_legal_moves(bitboard, valid_moves)
+        if castling.is_empty() {
+            castling.push('-');
         }
+
+        let en_passant = match self.en_passant {
+            Some(EnPassant(_, target)) => target.to_string(),
+            None => "-".to_string(),
+        };
+
+        format!(
+            "{placement} {turn} {castling} {en_passant} {} {}",
+            self.halfmove_clock, self.fullmove_number
+        )
     }
 
-    fn get_queen_pseudo_moves(&mut self, piece_pos: Option<u8>, side: usize) -> u64 {
-        let bitboard: u64;
-        let mut own_side: u64 = 0;
-        let mut opp_side: u64 = 0;
-        for (i, pieces) in self.board[side].iter().enumerate() {
-            own_side = own_side | pieces;
-            opp_side = opp_side | self.board[Side::get_opposite(side)][i];
-        }
+    pub fn get_piece_at(&self, square: Square) -> Option<Piece> {
+        self.mailbox[square.index() as usize]
+    }
 
-        match piece_pos {
-            Some(n) => bitboard = 1 << n,
-            None => bitboard = self.board[side][PieceType::QUEEN],
-        };
+    pub fn get_pieces(&self, side: Side, piece_type: PieceType) -> Bitboard {
+        self.board[side as usize][piece_type as usize]
+    }
 
-        let mut valid_moves = 0;
-
-        for n in 0..=63 {
-            if get_nth_bit(bitboard, n as u8) == 1 {
-                let pos = (2 as u64).pow(n);
-                let get_spot = |i: usize, j: usize| {
-                    if i == 0 && pos & CLEAR_FILE[7] != 0 {
-                        pos << 7 * j // top right
-                    } else if i == 1 && pos & CLEAR_FILE[7] != 0 {
-                        pos >> 9 * j // bottom right
-                    } else if i == 2 && pos & CLEAR_FILE[0] != 0 {
-                        pos >> 7 * j // bottom left
-                    } else if i == 3 && pos & CLEAR_FILE[0] != 0 {
-                        pos << 9 * j // top left
-                    } else if i == 4 && pos & CLEAR_FILE[7] != 0 {
-                        pos >> 1 * j // right
-                    } else if i == 5 && pos & CLEAR_FILE[0] != 0 {
-                        pos << 1 * j // left
-                    } else if i == 6 {
-                        pos << 8 * j // up
-                    } else if i == 7 {
-                        pos >> 8 * j // down
-                    } else {
-                        0
-                    }
-                };
+    fn put_piece(&mut self, piece: Piece, square: Square) {
+        self.board[piece.side as usize][piece.piece_type as usize] |= square.bit();
+        self.mailbox[square.index() as usize] = Some(piece);
+        self.occupancy[piece.side as usize] |= square.bit();
+        self.occupied |= square.bit();
+        self.hash ^= zobrist::piece(piece, square);
+    }
 
-                for i in 0..=7 {
-                    for j in 1..=7 {
-                        let spot = get_spot(i, j);
-                        if spot & own_side != 0 {
-                            // There is a piece of same side on the way bllocking
-                            break;
-                        }
-                        valid_moves = valid_moves | spot;
-
-                        if ((i == 0 || i == 1 || i == 4) && spot & CLEAR_FILE[7] == 0) // checking overflow
-                    || ((i == 2 || i == 3|| i == 5) && spot & CLEAR_FILE[0] == 0) // checking overflow
-                    || spot & opp_side != 0
-                        // checking if there is an opponent piece to capture
-                        {
-                            break;
-                        }
-                    }
-                }
+    fn remove_piece(&mut self, piece: Piece, square: Square) {
+        self.board[piece.side as usize][piece.piece_type as usize] &= !square.bit();
+        self.mailbox[square.index() as usize] = None;
+        self.occupancy[piece.side as usize] &= !square.bit();
+        self.occupied &= !square.bit();
+        self.hash ^= zobrist::piece(piece, square);
+    }
+
+    pub fn get_side_pieces(&self, side: Side) -> Bitboard {
+        self.occupancy[side as usize]
+    }
+
+    pub fn get_occupied(&self) -> Bitboard {
+        self.occupied
+    }
+
+    pub fn legal_moves(&self, i: Square, turn: Option<Side>) -> Bitboard {
+        match self.get_piece_at(i) {
+            Some(piece) if turn.is_none_or(|turn| turn == piece.side) => {
+                let moves = self.get_pseudo_moves(i);
+                self.get_legal_moves(i, moves)
             }
+            _ => Bitboard::EMPTY,
         }
-        if piece_pos.is_none() {
-            valid_moves
-        } else {
-            self.get_legal_moves(bitboard, valid_moves)
+    }
+
+    // Squares the piece could move to if leaving its own king in check were allowed
+    pub fn get_pseudo_moves(&self, i: Square) -> Bitboard {
+        let piece = match self.get_piece_at(i) {
+            Some(piece) => piece,
+            None => return Bitboard::EMPTY,
+        };
+        match piece.piece_type {
+            PieceType::King => self.get_king_pseudo_moves(i, piece.side),
+            PieceType::Knight => self.get_knight_pseudo_moves(i, piece.side),
+            PieceType::Bishop => self.get_bishop_pseudo_moves(i, piece.side),
+            PieceType::Rook => self.get_rook_pseudo_moves(i, piece.side),
+            PieceType::Queen => self.get_queen_pseudo_moves(i, piece.side),
+            PieceType::Pawn => self.get_pawn_pseudo_moves(i, piece.side),
         }
     }
 
-    fn get_white_pawn_moves(&mut self, i: Option<u8>) -> u64 {
-        let pawns_pos: u64;
-        let mut white_pieces = 0;
-        let mut black_pieces = 0;
-        for (index, pieces) in self.board[Side::WHITE].iter().enumerate() {
-            white_pieces = white_pieces | pieces;
-            black_pieces = black_pieces | self.board[Side::BLACK][index];
+    fn get_king_pseudo_moves(&self, i: Square, side: Side) -> Bitboard {
+        let valid_moves = attacks::king_attacks(i) & !self.get_side_pieces(side);
+        valid_moves | self.get_castling_moves(i, side)
+    }
+
+    // Squares the king can castle to. Passing through or starting from an attacked square is
+    // rejected here, landing on one is rejected by get_legal_moves like any other king move.
+    fn get_castling_moves(&self, king_pos: Square, side: Side) -> Bitboard {
+        if self.is_in_check(side) {
+            return Bitboard::EMPTY;
         }
+        self.get_castling_targets(king_pos, side)
+            .into_iter()
+            .filter(|to| {
+                let passed = Square::from_file_rank((king_pos.file() + to.file()) / 2, to.rank());
+                !self.is_square_attacked(passed.unwrap(), side.get_opposite())
+            })
+            .collect()
+    }
 
-        match i {
-            Some(n) => pawns_pos = 1 << n,
-            None => pawns_pos = self.board[Side::WHITE][PieceType::PAWN],
+    // Squares the king could castle to if attacks didn't matter: it still has the right, the
+    // king and rook are on their starting squares and everything between them is empty
+    fn get_castling_targets(&self, king_pos: Square, side: Side) -> Bitboard {
+        let (king_side, queen_side) = Castling::get_rights(side);
+        let back_rank = match side {
+            Side::White => 0,
+            Side::Black => 7,
         };
+        let square = |file| Square::from_file_rank(file, back_rank).unwrap();
+        if self.castling & (king_side | queen_side) == 0 || king_pos != square(4) {
+            return Bitboard::EMPTY;
+        }
+
+        let occupied = self.get_occupied();
+        let rooks = self.get_pieces(side, PieceType::Rook);
+        let mut moves = Bitboard::EMPTY;
 
-        let one_step = pawns_pos << 8 & !white_pieces & !black_pieces;
-        let two_step = (one_step & !CLEAR_RANK[2]) << 8 & !white_pieces & !black_pieces;
-        let valid_moves = one_step | two_step;
+        // f and g files must be empty
+        if self.castling & king_side != 0
+            && rooks.contains(square(7))
+            && (occupied & (square(5).bit() | square(6).bit())).is_empty()
+        {
+            moves |= square(6).bit();
+        }
+
+        // b, c and d files must be empty
+        if self.castling & queen_side != 0
+            && rooks.contains(square(0))
+            && (occupied & (square(1).bit() | square(2).bit() | square(3).bit())).is_empty()
+        {
+            moves |= square(2).bit();
+        }
+        moves
+    }
+
+    fn get_knight_pseudo_moves(&self, i: Square, side: Side) -> Bitboard {
+        attacks::knight_attacks(i) & !self.get_side_pieces(side)
+    }
+
+    fn get_bishop_pseudo_moves(&self, i: Square, side: Side) -> Bitboard {
+        attacks::bishop_attacks(i, self.get_occupied()) & !self.get_side_pieces(side)
+    }
+
+    fn get_rook_pseudo_moves(&self, i: Square, side: Side) -> Bitboard {
+        attacks::rook_attacks(i, self.get_occupied()) & !self.get_side_pieces(side)
+    }
+
+    fn get_queen_pseudo_moves(&self, i: Square, side: Side) -> Bitboard {
+        attacks::queen_attacks(i, self.get_occupied()) & !self.get_side_pieces(side)
+    }
+
+    fn get_pawn_pseudo_moves(&self, i: Square, side: Side) -> Bitboard {
+        let empty = !self.get_occupied();
+        let pawn_pos = i.bit();
+
+        let (one_step, two_step) = match side {
+            Side::White => {
+                let one_step = pawn_pos.north() & empty;
+                (one_step, (one_step & Bitboard::RANK_3).north() & empty)
+            }
+            Side::Black => {
+                let one_step = pawn_pos.south() & empty;
+                (one_step, (one_step & Bitboard::RANK_6).south() & empty)
+            }
+        };
 
         // Attacks
-        let left_attack = (pawns_pos & CLEAR_FILE[7]) << 7;
-        let right_attack = (pawns_pos & CLEAR_FILE[0]) << 9;
-        let attacks = (left_attack | right_attack) & black_pieces;
-        if i.is_none() {
-            // This will be only called from is_in_check function
-            attacks
-        } else {
-            self.get_legal_moves(pawns_pos, valid_moves | attacks)
+        let targets = self.get_side_pieces(side.get_opposite()) | self.get_en_passant_target(side);
+        let attacks = attacks::pawn_attacks(side, i) & targets;
+        one_step | two_step | attacks
+    }
+
+    // En passant target square that pawns of the given side can capture on, if any
+    fn get_en_passant_target(&self, side: Side) -> Bitboard {
+        match (self.en_passant, side) {
+            (Some(EnPassant(_, target)), Side::White) if target.rank() == 5 => target.bit(),
+            (Some(EnPassant(_, target)), Side::Black) if target.rank() == 2 => target.bit(),
+            _ => Bitboard::EMPTY,
         }
     }
 
-    fn get_black_pawn_moves(&mut self, i: Option<u8>) -> u64 {
-        let pawns_pos: u64;
-        let mut white_pieces = 0;
-        let mut black_pieces = 0;
-        for (index, pieces) in self.board[Side::WHITE].iter().enumerate() {
-            white_pieces = white_pieces | pieces;
-            black_pieces = black_pieces | self.board[Side::BLACK][index];
+    pub fn is_square_attacked(&self, i: Square, side: Side) -> bool {
+        !self.get_attackers(i, side, self.get_occupied()).is_empty()
+    }
+
+    // Pieces of the given side that attack the square. Sliders are blocked by the given
+    // occupancy rather than the board's, and pieces outside of it are left out, so exchanges
+    // can be played out by taking pieces off the occupancy one at a time.
+    pub fn get_attackers(&self, i: Square, side: Side, occupied: Bitboard) -> Bitboard {
+        let pieces = |piece_type| self.get_pieces(side, piece_type);
+        let diagonal = pieces(PieceType::Bishop) | pieces(PieceType::Queen);
+        let straight = pieces(PieceType::Rook) | pieces(PieceType::Queen);
+
+        // A piece attacks the square exactly when the same piece on the square would attack it
+        let attackers = (attacks::pawn_attacks(side.get_opposite(), i) & pieces(PieceType::Pawn))
+            | (attacks::knight_attacks(i) & pieces(PieceType::Knight))
+            | (attacks::king_attacks(i) & pieces(PieceType::King))
+            | (attacks::bishop_attacks(i, occupied) & diagonal)
+            | (attacks::rook_attacks(i, occupied) & straight);
+        attackers & occupied
+    }
+
+    pub fn is_in_check(&self, side: Side) -> bool {
+        match self.get_pieces(side, PieceType::King).lsb() {
+            Some(king) => self.is_square_attacked(king, side.get_opposite()),
+            None => false,
         }
+    }
+
+    fn get_legal_moves(&self, from: Square, moves: Bitboard) -> Bitboard {
+        moves
+            .into_iter()
+            .filter(|to| self.is_king_safe_after(from, *to))
+            .collect()
+    }
 
-        match i {
-            Some(n) => pawns_pos = 1 << n,
-            None => pawns_pos = self.board[Side::BLACK][PieceType::PAWN],
+    // Whether moving the piece leaves its own king out of check. Worked out from the occupancy
+    // after the move instead of playing it, so the position is left alone.
+    fn is_king_safe_after(&self, from: Square, to: Square) -> bool {
+        let piece = match self.get_piece_at(from) {
+            Some(piece) => piece,
+            None => return false,
         };
+        let mut occupied = (self.get_occupied() & !from.bit()) | to.bit();
+        // Whatever was captured can't give check any more
+        let mut captured = to.bit();
+        if self.get_move_flags(from, to) & MoveFlag::EN_PASSANT != 0 {
+            let pawn_pos = Square::from_file_rank(to.file(), from.rank()).unwrap();
+            occupied &= !pawn_pos.bit();
+            captured |= pawn_pos.bit();
+        }
+        let king = match piece.piece_type {
+            PieceType::King => to,
+            _ => match self.get_pieces(piece.side, PieceType::King).lsb() {
+                Some(king) => king,
+                None => return true,
+            },
+        };
+        (self.get_attackers(king, piece.side.get_opposite(), occupied) & !captured).is_empty()
+    }
 
-        let one_step = pawns_pos >> 8 & !black_pieces & !white_pieces;
-        let two_step = (one_step & !CLEAR_RANK[5]) >> 8 & !black_pieces & !white_pieces;
-        let valid_moves = one_step | two_step;
+    // Plays a move without checking that it is legal. The returned UndoInfo must be passed back
+    // to unmake_move to take it back.
+    pub fn make_move(&mut self, mv: Move) -> UndoInfo {
+        let undo = self.apply_move(mv);
+        self.history.push(self.hash);
+        undo
+    }
 
-        // Attacks
-        let right_attack = (pawns_pos & CLEAR_FILE[0]) >> 7;
-        let left_attack = (pawns_pos & CLEAR_FILE[7]) >> 9;
-        let attacks = (left_attack | right_attack) & white_pieces;
+    pub fn unmake_move(&mut self, mv: Move, undo: UndoInfo) {
+        self.history.pop();
+        self.revert_move(mv, undo);
+    }
+
+    // Everything make_move does except recording the position for repetitions
+    fn apply_move(&mut self, mv: Move) -> UndoInfo {
+        let (from, to) = (mv.from, mv.to);
+        let piece = self.get_piece_at(from).unwrap();
+        let side = piece.side;
+        let mut undo = UndoInfo {
+            captured: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+        };
+        // Pieces update the hash as they move, everything else is swapped out here and back in
+        // once the move is done
+        self.hash ^= self.get_state_hash();
+        // The en passant target square only lives for a single move
+        self.en_passant = None;
+
+        // Checking removal of pieces
+        if mv.is_en_passant() {
+            // The captured pawn is not on the target square
+            let pawn_pos = Square::from_file_rank(to.file(), from.rank()).unwrap();
+            self.remove_piece(Piece::new(side.get_opposite(), PieceType::Pawn), pawn_pos);
+            undo.captured = Some(PieceType::Pawn);
+        } else if let Some(removed_piece) = self.get_piece_at(to) {
+            // a piece is taken by the opponent
+            self.remove_piece(removed_piece, to);
+            undo.captured = Some(removed_piece.piece_type);
+        }
+
+        self.remove_piece(piece, from);
+        match mv.promotion {
+            Some(promotion) => self.put_piece(Piece::new(side, promotion), to),
+            None => self.put_piece(piece, to),
+        }
+
+        // Castling, the king moves two squares and the rook jumps over it
+        if mv.is_castle() {
+            let (rook_from, rook_to) = Self::get_castling_rook_squares(to);
+            let rook = Piece::new(side, PieceType::Rook);
+            self.remove_piece(rook, rook_from);
+            self.put_piece(rook, rook_to);
+        }
 
-        if i.is_none() {
-            // This will be only called from is_in_check function
-            attacks
+        // Set en passant target square
+        if mv.is_double_push() {
+            let target = Square::from_file_rank(from.file(), (from.rank() + to.rank()) / 2);
+            self.en_passant = Some(EnPassant(to, target.unwrap()));
+        }
+
+        self.update_castling_rights(from, to);
+
+        if piece.piece_type == PieceType::Pawn || undo.captured.is_some() {
+            self.halfmove_clock = 0;
         } else {
-            self.get_legal_moves(pawns_pos, valid_moves | attacks)
+            self.halfmove_clock += 1;
         }
+        if side == Side::Black {
+            self.fullmove_number += 1;
+        }
+        self.turn = side.get_opposite();
+        self.hash ^= self.get_state_hash();
+        undo
     }
 
-    pub fn is_in_check(&mut self, side: usize) -> bool {
-        let opp_side = Side::get_opposite(side);
-        let mut opp_moves = self.get_rook_pseudo_moves(None, opp_side)
-            | self.get_bishop_pseudo_moves(None, opp_side)
-            | self.get_king_pseudo_moves(None, opp_side)
-            | self.get_queen_pseudo_moves(None, opp_side)
-            | self.get_knight_pseudo_moves(None, opp_side);
-        if side == Side::WHITE {
-            opp_moves = opp_moves | self.get_black_pawn_moves(None);
+    fn revert_move(&mut self, mv: Move, undo: UndoInfo) {
+        let (from, to) = (mv.from, mv.to);
+        let side = self.turn.get_opposite();
+        let piece = self.get_piece_at(to).unwrap();
+
+        self.remove_piece(piece, to);
+        if mv.promotion.is_some() {
+            self.put_piece(Piece::new(side, PieceType::Pawn), from);
         } else {
-            opp_moves = opp_moves | self.get_white_pawn_moves(None);
+            self.put_piece(piece, from);
+        }
+
+        if mv.is_castle() {
+            let (rook_from, rook_to) = Self::get_castling_rook_squares(to);
+            let rook = Piece::new(side, PieceType::Rook);
+            self.remove_piece(rook, rook_to);
+            self.put_piece(rook, rook_from);
+        }
+
+        if let Some(captured) = undo.captured {
+            let captured_pos = if mv.is_en_passant() {
+                Square::from_file_rank(to.file(), from.rank()).unwrap()
+            } else {
+                to
+            };
+            self.put_piece(Piece::new(side.get_opposite(), captured), captured_pos);
+        }
+
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash = undo.hash;
+        if side == Side::Black {
+            self.fullmove_number -= 1;
         }
+        self.turn = side;
+    }
 
-        if opp_moves & self.board[side][PieceType::KING] != 0 {
-            true
+    fn get_castling_rook_squares(king_to: Square) -> (Square, Square) {
+        let square = |file| Square::from_file_rank(file, king_to.rank()).unwrap();
+        if king_to.file() == 6 {
+            (square(7), square(5))
         } else {
-            false
+            (square(0), square(3))
         }
     }
 
-    fn get_legal_moves(&mut self, from: u64, moves: u64) -> u64 {
-        let og_chess = self.clone();
-        let from = (from.ilog2()) as u8;
-        let piece = self.get_piece_at(from);
-        if piece.is_none() {
-            return 0;
+    // Moving the king or a rook, or capturing a rook, loses the matching castling rights
+    fn update_castling_rights(&mut self, from: Square, to: Square) {
+        for square in [from, to] {
+            self.castling &= match square {
+                Square::H1 => !Castling::WHITE_KING_SIDE,
+                Square::E1 => !(Castling::WHITE_KING_SIDE | Castling::WHITE_QUEEN_SIDE),
+                Square::A1 => !Castling::WHITE_QUEEN_SIDE,
+                Square::H8 => !Castling::BLACK_KING_SIDE,
+                Square::E8 => !(Castling::BLACK_KING_SIDE | Castling::BLACK_QUEEN_SIDE),
+                Square::A8 => !Castling::BLACK_QUEEN_SIDE,
+                _ => Castling::ALL,
+            };
         }
-        let side = piece.unwrap().side;
-        let mut legal_moves = 0;
+    }
 
-        for pos in 0..=63 {
-            if get_nth_bit(moves, pos) != 1 {
-                continue;
+    // Every legal move of the side to move, with one move per promotion piece
+    pub fn generate_legal_moves(&mut self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for from in self.get_side_pieces(self.turn) {
+            for to in self.legal_moves(from, Some(self.turn)) {
+                self.push_moves(&mut moves, from, to);
             }
+        }
+        moves
+    }
 
-            self.make_move(from, pos);
+    // The legal captures and promotions, so a search can try them before generating the rest
+    pub fn generate_legal_captures(&mut self) -> Vec<Move> {
+        self.generate_staged_moves(true)
+    }
+
+    // The legal moves generate_legal_captures leaves out
+    pub fn generate_legal_quiets(&mut self) -> Vec<Move> {
+        self.generate_staged_moves(false)
+    }
 
-            if !self.is_in_check(side) {
-                legal_moves = legal_moves | (1 << pos);
+    fn generate_staged_moves(&mut self, captures: bool) -> Vec<Move> {
+        let side = self.turn;
+        let enemies = self.get_side_pieces(side.get_opposite());
+        let pawns = self.get_pieces(side, PieceType::Pawn);
+        let mut moves = Vec::new();
+        for from in self.get_side_pieces(side) {
+            let mut tactical = enemies;
+            if pawns.contains(from) {
+                tactical |= self.get_en_passant_target(side) | Bitboard::RANK_1 | Bitboard::RANK_8;
             }
+            let pseudo_moves = self.get_pseudo_moves(from);
+            let targets = if captures {
+                pseudo_moves & tactical
+            } else {
+                pseudo_moves & !tactical
+            };
+            for to in self.get_legal_moves(from, targets) {
+                self.push_moves(&mut moves, from, to);
+            }
+        }
+        moves
+    }
 
-            self.clone_from(&og_chess);
+    fn push_moves(&self, moves: &mut Vec<Move>, from: Square, to: Square) {
+        let flags = self.get_move_flags(from, to);
+        if self.is_promotion(from, to) {
+            for piece_type in PieceType::PROMOTIONS {
+                moves.push(Move::new(from, to, Some(piece_type), flags));
+            }
+        } else {
+            moves.push(Move::new(from, to, None, flags));
         }
-        return legal_moves;
     }
 
-    fn make_move(&mut self, from: u8, to: u8) {
-        let piece = self.get_piece_at(from);
-        let side;
-        let piece_type;
-        let current_en_passant = self.en_passant; // Store the current en passant target square as it gets overwritten later
-        match piece {
-            // Special case for pawns to add en passant
-            Some(Piece {
-                piece_type: PieceType::PAWN,
-                side: pawn_side,
-            }) => {
-                side = pawn_side;
-                piece_type = PieceType::PAWN;
-                // Set en passant target square
-                if to.abs_diff(from) == 16 {
-                    if side == Side::WHITE {
-                        self.en_passant = Some(EnPassant(to, from + 8));
-                    } else {
-                        self.en_passant = Some(EnPassant(to, from - 8));
-                    }
-                }
+    fn get_move_flags(&self, from: Square, to: Square) -> u8 {
+        let piece_type = match self.get_piece_at(from) {
+            Some(piece) => piece.piece_type,
+            None => return MoveFlag::QUIET,
+        };
+        let mut flags = MoveFlag::QUIET;
+        if self.get_piece_at(to).is_some() {
+            flags |= MoveFlag::CAPTURE;
+        }
+        if piece_type == PieceType::Pawn {
+            if to.rank().abs_diff(from.rank()) == 2 {
+                flags |= MoveFlag::DOUBLE_PUSH;
+            } else if to.file() != from.file() && self.get_piece_at(to).is_none() {
+                flags |= MoveFlag::CAPTURE | MoveFlag::EN_PASSANT;
             }
-            Some(piece) => {
-                side = piece.side;
-                piece_type = piece.piece_type;
-                // Unset en passant target square
-                self.en_passant = None;
+        }
+        if piece_type == PieceType::King && to.file().abs_diff(from.file()) == 2 {
+            flags |= MoveFlag::CASTLE;
+        }
+        flags
+    }
+
+    // Counts the leaf nodes of the legal move tree, used to validate the move generator
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.generate_legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for mv in moves {
+            let undo = self.make_move(mv);
+            nodes += self.perft(depth - 1);
+            self.unmake_move(mv, undo);
+        }
+        nodes
+    }
+
+    // Perft split by root move, handy for finding where two generators disagree
+    pub fn divide(&mut self, depth: u32) -> Vec<(Move, u64)> {
+        let mut divided = Vec::new();
+        for mv in self.generate_legal_moves() {
+            let undo = self.make_move(mv);
+            divided.push((mv, self.perft(depth.saturating_sub(1))));
+            self.unmake_move(mv, undo);
+        }
+        divided
+    }
+
+    pub fn has_legal_moves(&self) -> bool {
+        for i in self.get_side_pieces(self.turn) {
+            if !self.legal_moves(i, Some(self.turn)).is_empty() {
+                return true;
             }
-            _ => return,
+        }
+        false
+    }
+
+    pub fn status(&self) -> GameStatus {
+        if !self.has_legal_moves() {
+            if self.is_in_check(self.turn) {
+                GameStatus::Checkmate(self.turn.get_opposite())
+            } else {
+                GameStatus::Stalemate
+            }
+        } else if self.has_insufficient_material() {
+            GameStatus::Draw(DrawReason::InsufficientMaterial)
+        } else if self.get_repetition_count() >= 5 {
+            GameStatus::Draw(DrawReason::FivefoldRepetition)
+        } else if self.halfmove_clock >= 150 {
+            GameStatus::Draw(DrawReason::SeventyFiveMoveRule)
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
+    pub fn is_promotion(&self, from: Square, to: Square) -> bool {
+        let pawns = self.get_pieces(Side::White, PieceType::Pawn)
+            | self.get_pieces(Side::Black, PieceType::Pawn);
+        pawns.contains(from) && (to.rank() == 0 || to.rank() == 7)
+    }
+
+    // The legal move from one square to another, or why there is none
+    pub fn find_legal_move(
+        &mut self,
+        from: Square,
+        to: Square,
+        promotion: Option<PieceType>,
+    ) -> Result<Move, MoveError> {
+        match self.get_piece_at(from) {
+            Some(piece) if piece.side != self.turn => return Err(MoveError::NotYourTurn(from)),
+            Some(_) => {}
+            None => return Err(MoveError::NoPiece(from)),
+        }
+        if !self.get_pseudo_moves(from).contains(to) {
+            // A castle that only attacks stand in the way is a matter of check
+            let king = self.get_pieces(self.turn, PieceType::King);
+            if king.contains(from) && self.get_castling_targets(from, self.turn).contains(to) {
+                return Err(MoveError::LeavesKingInCheck(from, to));
+            }
+            return Err(MoveError::UnreachableSquare(from, to));
+        }
+        if !self.get_legal_moves(from, to.bit()).contains(to) {
+            return Err(MoveError::LeavesKingInCheck(from, to));
+        }
+        let promotion_error = match promotion {
+            Some(piece_type) if !PieceType::PROMOTIONS.contains(&piece_type) => {
+                Some(PromotionError::InvalidPiece)
+            }
+            Some(_) if !self.is_promotion(from, to) => Some(PromotionError::NotAPromotion),
+            None if self.is_promotion(from, to) => Some(PromotionError::MissingPiece),
+            _ => None,
         };
-        let mut own_side = 0;
-        let mut opp_side = 0;
-        for (index, pieces) in self.board[side].iter().enumerate() {
-            own_side = own_side | pieces;
-            opp_side = opp_side | self.board[Side::get_opposite(side)][index];
+        if let Some(err) = promotion_error {
+            return Err(MoveError::InvalidPromotion(err));
         }
+        Ok(Move::new(
+            from,
+            to,
+            promotion,
+            self.get_move_flags(from, to),
+        ))
+    }
 
-        // Update the selected piece's position with the moved position
-        own_side = own_side | ((self.board[side][piece_type] & !(1 << from)) | (1 << (to)));
+    // Plays the move if it is legal and reports what it did
+    pub fn try_move(
+        &mut self,
+        from: Square,
+        to: Square,
+        promotion: Option<PieceType>,
+    ) -> Result<MoveOutcome, MoveError> {
+        let mv = self.find_legal_move(from, to, promotion)?;
+        let undo = self.make_move(mv);
+        Ok(MoveOutcome {
+            mv,
+            undo,
+            captured: undo.captured,
+            promotion: mv.promotion,
+            is_check: self.is_in_check(self.turn),
+            status: self.status(),
+        })
+    }
 
-        // Checking removal of pieces
-        if get_nth_bit(own_side, to) == get_nth_bit(opp_side, to)
-            || (current_en_passant.is_some() && current_en_passant.unwrap().1 == to)
-        {
-            let removed_piece = self.get_piece_at(to);
-            match removed_piece {
-                Some(removed_piece) => {
-                    // a piece is taken by the opponent
-                    self.board[removed_piece.side][removed_piece.piece_type] =
-                        self.board[removed_piece.side][removed_piece.piece_type] & !(1 << to);
-                }
-                None => {
-                    // En passant
-                    let target_square = self.get_piece_at(current_en_passant.unwrap().0).unwrap();
-                    self.board[target_square.side][target_square.piece_type] = self.board
-                        [target_square.side][target_square.piece_type]
-                        & !(1 << current_en_passant.unwrap().0);
+    pub fn get_hash(&self) -> u64 {
+        self.hash
+    }
+
+    // The hash from scratch, the incrementally updated one from get_hash should always match it
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = self.get_state_hash();
+        for side in [Side::Black, Side::White] {
+            for piece_type in PieceType::ALL {
+                for square in self.get_pieces(side, piece_type) {
+                    hash ^= zobrist::piece(Piece::new(side, piece_type), square);
                 }
             }
         }
+        hash
+    }
+
+    // Hash of the side to move, castling rights and en passant file
+    fn get_state_hash(&self) -> u64 {
+        let mut hash = zobrist::turn(self.turn) ^ zobrist::castling(self.castling);
+        // The en passant file only counts when a pawn could actually capture there
+        if let Some(target) = self.get_en_passant_target(self.turn).lsb() {
+            let capturers = attacks::pawn_attacks(self.turn.get_opposite(), target);
+            if !(capturers & self.get_pieces(self.turn, PieceType::Pawn)).is_empty() {
+                hash ^= zobrist::en_passant(target.file());
+            }
+        }
+        hash
+    }
 
-        self.board[side][piece_type] = (self.board[side][piece_type] & !(1 << from)) | (1 << (to));
+    pub fn get_repetition_count(&self) -> usize {
+        // Pawn moves and captures can't be undone, so earlier positions can never repeat
+        let reversible = self.history.len().min(self.halfmove_clock as usize + 1);
+        let recent = &self.history[self.history.len() - reversible..];
+        match recent.last() {
+            Some(current) => recent.iter().filter(|hash| *hash == current).count(),
+            None => 0,
+        }
     }
 
-    pub fn move_piece(&mut self, from: u8, to: u8) {
-        if get_nth_bit(self.legal_moves(from, Some(self.turn)), to) == 0 {
-            return;
+    pub fn has_insufficient_material(&self) -> bool {
+        let mut bishops = Bitboard::EMPTY;
+        let mut minor_pieces = 0;
+        for side in [Side::White, Side::Black] {
+            let pieces = |piece_type| self.get_pieces(side, piece_type);
+            let mating_material =
+                pieces(PieceType::Pawn) | pieces(PieceType::Rook) | pieces(PieceType::Queen);
+            if !mating_material.is_empty() {
+                return false;
+            }
+            bishops |= pieces(PieceType::Bishop);
+            minor_pieces += (pieces(PieceType::Bishop) | pieces(PieceType::Knight)).count();
+        }
+
+        // A lone minor piece, or bishops that all share a square colour, can never mate
+        minor_pieces <= 1
+            || (minor_pieces == bishops.count()
+                && ((bishops & Bitboard::DARK_SQUARES).is_empty()
+                    || (bishops & Bitboard::LIGHT_SQUARES).is_empty()))
+    }
+
+    pub fn can_claim_draw(&self) -> Option<DrawReason> {
+        if self.get_repetition_count() >= 3 {
+            Some(DrawReason::ThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(DrawReason::FiftyMoveRule)
+        } else {
+            None
         }
-        self.make_move(from, to);
-        self.turn = Side::get_opposite(self.turn);
     }
 }
 
@@ -604,3 +1170,471 @@ impl Default for Chess {
         Self::new()
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Node counts from https://www.chessprogramming.org/Perft_Results
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED: &str =
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn perft(fen: &str, depth: u32) -> u64 {
+        Chess::from_fen(fen).unwrap().perft(depth)
+    }
+
+    #[test]
+    fn perft_initial_position() {
+        assert_eq!(perft(STARTING_FEN, 1), 20);
+        assert_eq!(perft(STARTING_FEN, 2), 400);
+        assert_eq!(perft(STARTING_FEN, 3), 8902);
+        assert_eq!(perft(STARTING_FEN, 4), 197281);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        assert_eq!(perft(KIWIPETE, 1), 48);
+        assert_eq!(perft(KIWIPETE, 2), 2039);
+        assert_eq!(perft(KIWIPETE, 3), 97862);
+    }
+
+    #[test]
+    fn perft_position_3() {
+        assert_eq!(perft(POSITION_3, 1), 14);
+        assert_eq!(perft(POSITION_3, 2), 191);
+        assert_eq!(perft(POSITION_3, 3), 2812);
+        assert_eq!(perft(POSITION_3, 4), 43238);
+    }
+
+    #[test]
+    fn perft_position_4() {
+        assert_eq!(perft(POSITION_4, 1), 6);
+        assert_eq!(perft(POSITION_4, 2), 264);
+        assert_eq!(perft(POSITION_4, 3), 9467);
+        assert_eq!(perft(POSITION_4_MIRRORED, 3), 9467);
+    }
+
+    #[test]
+    fn perft_position_5() {
+        assert_eq!(perft(POSITION_5, 1), 44);
+        assert_eq!(perft(POSITION_5, 2), 1486);
+        assert_eq!(perft(POSITION_5, 3), 62379);
+    }
+
+    #[test]
+    fn perft_position_6() {
+        assert_eq!(perft(POSITION_6, 1), 46);
+        assert_eq!(perft(POSITION_6, 2), 2079);
+        assert_eq!(perft(POSITION_6, 3), 89890);
+    }
+
+    #[test]
+    #[ignore = "slow, run with cargo test --release -- --ignored"]
+    fn perft_deep() {
+        assert_eq!(perft(STARTING_FEN, 5), 4865609);
+        assert_eq!(perft(KIWIPETE, 4), 4085603);
+        assert_eq!(perft(POSITION_3, 5), 674624);
+        assert_eq!(perft(POSITION_4, 4), 422333);
+        assert_eq!(perft(POSITION_5, 4), 2103487);
+        assert_eq!(perft(POSITION_6, 4), 3894594);
+    }
+
+    #[test]
+    fn rejects_invalid_fens() {
+        let invalid = [
+            ("8/8/8/8/8/8/8/8", FenError::WrongFieldCount(1)),
+            ("4k3/8/8/8/8/8/4K3 w - -", FenError::WrongRankCount(7)),
+            (
+                "4k3/8/8/8/8/8/8/4K4 w - -",
+                FenError::InvalidRank("4K4".into()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K2 w - -",
+                FenError::InvalidRank("4K2".into()),
+            ),
+            (
+                "4k3/08/8/8/8/8/8/4K3 w - -",
+                FenError::InvalidRank("08".into()),
+            ),
+            (
+                "4k3/8/8/44/8/8/8/4K3 w - -",
+                FenError::InvalidRank("44".into()),
+            ),
+            ("4k3/8/8/8/8/8/8/4X3 w - -", FenError::InvalidPiece('X')),
+            ("4k3/8/8/8/8/8/8/8 w - -", FenError::InvalidKings),
+            ("4k3/8/8/8/8/8/8/3KK3 w - -", FenError::InvalidKings),
+            ("4k3/8/8/8/8/8/8/P3K3 w - -", FenError::PawnOnBackRank),
+            ("4k3/8/8/8/8/8/4R3/4K3 w - -", FenError::OpponentInCheck),
+            (
+                "4k3/8/8/8/8/8/8/4K3 x - -",
+                FenError::InvalidSide("x".into()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w KK -",
+                FenError::InvalidCastling("KK".into()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w X -",
+                FenError::InvalidCastling("X".into()),
+            ),
+            // Rights without the king or the rook on its starting square
+            (
+                "4k3/8/8/8/8/8/8/4K3 w KQkq - 0 1",
+                FenError::InvalidCastling("KQkq".into()),
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R3K1R1 w KQkq - 0 1",
+                FenError::InvalidCastling("KQkq".into()),
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R2K3R w Q - 0 1",
+                FenError::InvalidCastling("Q".into()),
+            ),
+            (
+                "1r2k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1",
+                FenError::InvalidCastling("Kq".into()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e3",
+                FenError::InvalidEnPassant("e3".into()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - z9",
+                FenError::InvalidEnPassant("z9".into()),
+            ),
+            // No pawn that could have just moved, or squares it passed over in the way
+            (
+                "4k3/8/8/8/3p4/8/8/4K3 b - e3 0 1",
+                FenError::InvalidEnPassant("e3".into()),
+            ),
+            (
+                "4k3/8/8/8/3pP3/4N3/8/4K3 b - e3 0 1",
+                FenError::InvalidEnPassant("e3".into()),
+            ),
+            (
+                "4k3/8/8/8/3pP3/8/4N3/4K3 b - e3 0 1",
+                FenError::InvalidEnPassant("e3".into()),
+            ),
+            (
+                "4k3/8/8/8/4K3/8/8/8 w - - x",
+                FenError::InvalidHalfmoveClock("x".into()),
+            ),
+            (
+                "4k3/8/8/8/4K3/8/8/8 w - - 0 0",
+                FenError::InvalidFullmoveNumber("0".into()),
+            ),
+        ];
+        for (fen, err) in invalid {
+            assert_eq!(Chess::from_fen(fen).map(|_| ()), Err(err), "{fen}");
+        }
+
+        let chess = Chess::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3").unwrap();
+        assert_eq!(chess.to_fen(), "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1");
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let mut chess = Chess::from_fen(KIWIPETE).unwrap();
+        let divided = chess.divide(2);
+        assert_eq!(divided.len(), 48);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+    }
+
+    #[test]
+    fn staged_generation_splits_legal_moves() {
+        for fen in [KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            let mut chess = Chess::from_fen(fen).unwrap();
+            let captures = chess.generate_legal_captures();
+            let quiets = chess.generate_legal_quiets();
+            assert!(captures
+                .iter()
+                .all(|mv| mv.is_capture() || mv.promotion.is_some()));
+            assert!(quiets
+                .iter()
+                .all(|mv| !mv.is_capture() && mv.promotion.is_none()));
+
+            let mut staged: Vec<Move> = captures.into_iter().chain(quiets).collect();
+            let mut moves = chess.generate_legal_moves();
+            let key = |mv: &Move| (mv.from, mv.to, mv.promotion.map(|p| p as u8));
+            staged.sort_by_key(key);
+            moves.sort_by_key(key);
+            assert_eq!(staged, moves, "{fen}");
+        }
+    }
+
+    #[test]
+    fn unmake_move_restores_position() {
+        for fen in [KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            let mut chess = Chess::from_fen(fen).unwrap();
+            for mv in chess.generate_legal_moves() {
+                let undo = chess.make_move(mv);
+                chess.unmake_move(mv, undo);
+                assert_eq!(chess.to_fen(), fen);
+            }
+        }
+    }
+
+    // The hash, mailbox and occupancy are all updated incrementally and must match the board
+    fn check_incremental_state(chess: &mut Chess, depth: u32) {
+        assert_eq!(chess.get_hash(), chess.compute_hash(), "{}", chess.to_fen());
+        let mut occupied = Bitboard::EMPTY;
+        for side in [Side::Black, Side::White] {
+            let mut side_pieces = Bitboard::EMPTY;
+            for piece_type in PieceType::ALL {
+                for square in chess.get_pieces(side, piece_type) {
+                    assert_eq!(
+                        chess.get_piece_at(square),
+                        Some(Piece::new(side, piece_type))
+                    );
+                }
+                side_pieces |= chess.get_pieces(side, piece_type);
+            }
+            assert_eq!(chess.get_side_pieces(side), side_pieces);
+            occupied |= side_pieces;
+        }
+        assert_eq!(chess.get_occupied(), occupied);
+        assert_eq!(
+            chess.mailbox.iter().flatten().count() as u32,
+            occupied.count()
+        );
+        if depth == 0 {
+            return;
+        }
+        let hash = chess.get_hash();
+        for mv in chess.generate_legal_moves() {
+            let undo = chess.make_move(mv);
+            check_incremental_state(chess, depth - 1);
+            chess.unmake_move(mv, undo);
+            assert_eq!(chess.get_hash(), hash);
+        }
+    }
+
+    #[test]
+    fn incremental_state_matches_board() {
+        for fen in [KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            check_incremental_state(&mut Chess::from_fen(fen).unwrap(), 2);
+        }
+    }
+
+    #[test]
+    fn transpositions_have_the_same_hash() {
+        let play = |moves: &[(Square, Square)]| {
+            let mut chess = Chess::new();
+            for (from, to) in moves {
+                chess.try_move(*from, *to, None).unwrap();
+            }
+            chess.get_hash()
+        };
+        let knights_first = play(&[
+            (Square::G1, Square::F3),
+            (Square::G8, Square::F6),
+            (Square::E2, Square::E4),
+        ]);
+        let pawn_first = play(&[
+            (Square::E2, Square::E4),
+            (Square::G8, Square::F6),
+            (Square::G1, Square::F3),
+        ]);
+        assert_eq!(knights_first, pawn_first);
+        assert_ne!(knights_first, Chess::new().get_hash());
+
+        // A double push only changes the hash when it can be captured en passant
+        let quiet_push = Chess::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+        let no_target = Chess::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(quiet_push.get_hash(), no_target.get_hash());
+        let capturable = Chess::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let too_late = Chess::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(capturable.get_hash(), too_late.get_hash());
+    }
+
+    fn play(chess: &mut Chess, moves: &[&str]) {
+        for uci in moves {
+            let mv = chess.parse_uci_move(uci).unwrap();
+            chess.make_move(mv);
+        }
+    }
+
+    #[test]
+    fn detects_mate_and_stalemate() {
+        let chess = Chess::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!chess.has_legal_moves());
+        assert!(!chess.is_in_check(Side::Black));
+        assert_eq!(chess.status(), GameStatus::Stalemate);
+
+        let chess = Chess::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(chess.status(), GameStatus::Checkmate(Side::White));
+    }
+
+    #[test]
+    fn detects_insufficient_material() {
+        let dead = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1",
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1", // Both bishops on dark squares
+        ];
+        for fen in dead {
+            let chess = Chess::from_fen(fen).unwrap();
+            assert!(chess.has_insufficient_material(), "{fen}");
+            assert_eq!(
+                chess.status(),
+                GameStatus::Draw(DrawReason::InsufficientMaterial)
+            );
+        }
+        // Knights can mate with help, and so can bishops on different colours
+        for fen in [
+            "1n2k3/8/8/8/8/8/8/1N2K3 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        ] {
+            let chess = Chess::from_fen(fen).unwrap();
+            assert!(!chess.has_insufficient_material(), "{fen}");
+            assert_eq!(chess.status(), GameStatus::Ongoing);
+        }
+    }
+
+    #[test]
+    fn detects_repetitions() {
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let mut chess = Chess::new();
+        assert_eq!(chess.get_repetition_count(), 1);
+        play(&mut chess, &shuffle);
+        assert_eq!(chess.get_repetition_count(), 2);
+        assert_eq!(chess.can_claim_draw(), None);
+
+        play(&mut chess, &shuffle);
+        assert_eq!(chess.get_repetition_count(), 3);
+        assert_eq!(
+            chess.can_claim_draw(),
+            Some(DrawReason::ThreefoldRepetition)
+        );
+        assert_eq!(chess.status(), GameStatus::Ongoing);
+
+        play(&mut chess, &shuffle);
+        play(&mut chess, &shuffle[..3]);
+        assert_eq!(chess.status(), GameStatus::Ongoing);
+        play(&mut chess, &shuffle[3..]);
+        assert_eq!(chess.get_repetition_count(), 5);
+        assert_eq!(
+            chess.status(),
+            GameStatus::Draw(DrawReason::FivefoldRepetition)
+        );
+    }
+
+    #[test]
+    fn counts_moves_without_progress() {
+        let mut chess = Chess::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+        assert_eq!(chess.can_claim_draw(), None);
+        play(&mut chess, &["a1a2"]);
+        assert_eq!(chess.halfmove_clock, 100);
+        assert_eq!(chess.can_claim_draw(), Some(DrawReason::FiftyMoveRule));
+        assert_eq!(chess.status(), GameStatus::Ongoing);
+
+        let mut chess = Chess::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 149 80").unwrap();
+        assert_eq!(chess.status(), GameStatus::Ongoing);
+        play(&mut chess, &["a1a2"]);
+        assert_eq!(
+            chess.status(),
+            GameStatus::Draw(DrawReason::SeventyFiveMoveRule)
+        );
+
+        // Mate on the last move still counts
+        let mut chess = Chess::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 149 80").unwrap();
+        play(&mut chess, &["a1a8"]);
+        assert_eq!(chess.halfmove_clock, 150);
+        assert_eq!(chess.status(), GameStatus::Checkmate(Side::White));
+    }
+
+    #[test]
+    fn try_move_reports_outcome_and_errors() {
+        // The e4 pawn is pinned by the rook on e8
+        let mut chess = Chess::from_fen("4r1k1/1P6/8/5b2/4P3/8/3n4/4K1NR w K - 0 1").unwrap();
+        let errors = [
+            (Square::E3, Square::E4, None, MoveError::NoPiece(Square::E3)),
+            (
+                Square::G8,
+                Square::H8,
+                None,
+                MoveError::NotYourTurn(Square::G8),
+            ),
+            (
+                Square::G1,
+                Square::G3,
+                None,
+                MoveError::UnreachableSquare(Square::G1, Square::G3),
+            ),
+            (
+                Square::E1,
+                Square::G1,
+                None,
+                MoveError::UnreachableSquare(Square::E1, Square::G1),
+            ),
+            (
+                Square::E4,
+                Square::F5,
+                None,
+                MoveError::LeavesKingInCheck(Square::E4, Square::F5),
+            ),
+            (
+                Square::B7,
+                Square::B8,
+                None,
+                MoveError::InvalidPromotion(PromotionError::MissingPiece),
+            ),
+            (
+                Square::B7,
+                Square::B8,
+                Some(PieceType::King),
+                MoveError::InvalidPromotion(PromotionError::InvalidPiece),
+            ),
+            (
+                Square::E4,
+                Square::E5,
+                Some(PieceType::Queen),
+                MoveError::InvalidPromotion(PromotionError::NotAPromotion),
+            ),
+        ];
+        for (from, to, promotion, err) in errors {
+            assert_eq!(chess.try_move(from, to, promotion).map(|_| ()), Err(err));
+        }
+        assert_eq!(chess.to_fen(), "4r1k1/1P6/8/5b2/4P3/8/3n4/4K1NR w K - 0 1");
+
+        let outcome = chess.try_move(Square::E1, Square::D2, None).unwrap();
+        assert_eq!(outcome.captured, Some(PieceType::Knight));
+        assert!(!outcome.is_check);
+        assert_eq!(outcome.status, GameStatus::Ongoing);
+        chess.unmake_move(outcome.mv, outcome.undo);
+
+        let outcome = chess
+            .try_move(Square::B7, Square::B8, Some(PieceType::Queen))
+            .unwrap();
+        assert_eq!(outcome.promotion, Some(PieceType::Queen));
+        assert_eq!(outcome.captured, None);
+
+        // Castling through, out of or into check is a check error, not an unreachable square
+        for (fen, to) in [
+            ("r3k2r/8/b7/8/8/8/8/R3K2R w KQkq - 0 1", Square::G1),
+            ("4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1", Square::G1),
+            ("4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1", Square::C1),
+            ("4k3/8/8/8/8/8/6r1/R3K2R w KQ - 0 1", Square::G1),
+        ] {
+            let mut chess = Chess::from_fen(fen).unwrap();
+            assert_eq!(
+                chess.try_move(Square::E1, to, None).map(|_| ()),
+                Err(MoveError::LeavesKingInCheck(Square::E1, to)),
+                "{fen}"
+            );
+        }
+        let mut chess = Chess::from_fen("r3k2r/8/b7/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert!(chess.try_move(Square::E1, Square::C1, None).is_ok());
+
+        let mut chess = Chess::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let outcome = chess.try_move(Square::A1, Square::A8, None).unwrap();
+        assert!(outcome.is_check);
+        assert_eq!(outcome.status, GameStatus::Checkmate(Side::White));
+    }
+}
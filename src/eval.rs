@@ -0,0 +1,609 @@
+// Static evaluation: material, piece-square tables, mobility, pawn structure and king safety.
+// Every term has a middlegame and an endgame weight, blended by how much material is left.
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+
+use crate::attacks;
+use crate::bitboard::Bitboard;
+use crate::chess::{Chess, PieceType, Side};
+use crate::square::Square;
+
+// Names of the piece-square tables in the text format, in PieceType order
+const PIECE_NAMES: [&str; 6] = ["pawn", "bishop", "knight", "rook", "queen", "king"];
+
+// Knights and bishops count 1, rooks 2 and queens 4, so the starting position has 24
+const MAX_PHASE: i32 = 24;
+
+// A middlegame and an endgame weight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const fn new(mg: i32, eg: i32) -> Score {
+        Score { mg, eg }
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, n: i32) -> Score {
+        Score::new(self.mg * n, self.eg * n)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+// Everything evaluate_with weighs, indexed by PieceType where there is one weight per piece.
+// Piece-square tables are laid out as a white diagram, a8 first and h1 last, and are flipped
+// for black.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalWeights {
+    pub material: [Score; 6],
+    pub psqt: [[Score; 64]; 6],
+    pub mobility: [Score; 6], // Per square the piece can move to
+    pub doubled_pawn: Score,  // Per pawn behind another one on the same file
+    pub isolated_pawn: Score,
+    pub passed_pawn: [Score; 8], // By rank, counted from the pawn's own side
+    pub pawn_shield: Score,      // Per pawn on the two ranks in front of the king
+    pub king_zone_attack: Score, // Per enemy attack on a square next to the king
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WeightsError {
+    UnknownWeight(String),
+    WrongValueCount(String, usize), // WrongValueCount(weight, values found)
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WeightsError::UnknownWeight(name) => write!(f, "unknown weight '{name}'"),
+            WeightsError::WrongValueCount(name, n) => {
+                write!(f, "wrong number of values for '{name}', found {n}")
+            }
+            WeightsError::InvalidValue(value) => write!(f, "invalid value '{value}'"),
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+const fn tapered_table(mg: [i32; 64], eg: [i32; 64]) -> [Score; 64] {
+    let mut table = [Score::new(0, 0); 64];
+    let mut i = 0;
+    while i < 64 {
+        table[i] = Score::new(mg[i], eg[i]);
+        i += 1;
+    }
+    table
+}
+
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    60,  60,  60,  60,  60,  60,  60,  60,
+    40,  40,  40,  40,  40,  40,  40,  40,
+    20,  20,  20,  20,  20,  20,  20,  20,
+    10,  10,  10,  10,  10,  10,  10,  10,
+     5,   5,   5,   5,   5,   5,   5,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK_MG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const ROOK_EG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    10,  10,  10,  10,  10,  10,  10,  10,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+// The king hides behind its pawns in the middlegame and heads for the centre in the endgame
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+impl EvalWeights {
+    pub const DEFAULT: EvalWeights = EvalWeights {
+        // Pawn, bishop, knight, rook, queen, king
+        material: [
+            Score::new(100, 120),
+            Score::new(330, 330),
+            Score::new(320, 300),
+            Score::new(500, 550),
+            Score::new(900, 950),
+            Score::new(0, 0),
+        ],
+        psqt: [
+            tapered_table(PAWN_MG, PAWN_EG),
+            tapered_table(BISHOP, BISHOP),
+            tapered_table(KNIGHT, KNIGHT),
+            tapered_table(ROOK_MG, ROOK_EG),
+            tapered_table(QUEEN, QUEEN),
+            tapered_table(KING_MG, KING_EG),
+        ],
+        mobility: [
+            Score::new(0, 0),
+            Score::new(3, 4),
+            Score::new(4, 4),
+            Score::new(2, 4),
+            Score::new(1, 2),
+            Score::new(0, 0),
+        ],
+        doubled_pawn: Score::new(-10, -20),
+        isolated_pawn: Score::new(-10, -15),
+        passed_pawn: [
+            Score::new(0, 0),
+            Score::new(5, 10),
+            Score::new(10, 15),
+            Score::new(15, 25),
+            Score::new(25, 45),
+            Score::new(40, 70),
+            Score::new(60, 110),
+            Score::new(0, 0),
+        ],
+        pawn_shield: Score::new(10, 0),
+        king_zone_attack: Score::new(-8, -2),
+    };
+
+    // Reads weights in the format to_text writes. Reading starts from the defaults, so a file
+    // only needs the weights it changes.
+    pub fn from_text(text: &str) -> Result<EvalWeights, WeightsError> {
+        let mut values: Vec<(&str, Vec<i32>)> = Vec::new();
+        let tokens = text
+            .lines()
+            .flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace());
+        for token in tokens {
+            if token.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                values.push((token, Vec::new()));
+                continue;
+            }
+            let value = token
+                .parse()
+                .map_err(|_| WeightsError::InvalidValue(token.to_string()))?;
+            match values.last_mut() {
+                Some((_, weight_values)) => weight_values.push(value),
+                None => return Err(WeightsError::InvalidValue(token.to_string())),
+            }
+        }
+
+        let mut weights = EvalWeights::DEFAULT;
+        let mut named = weights.get_named_weights();
+        for (name, weight_values) in values {
+            let scores = match named.iter_mut().find(|(weight, _)| weight == name) {
+                Some((_, scores)) => scores,
+                None => return Err(WeightsError::UnknownWeight(name.to_string())),
+            };
+            if weight_values.len() != scores.len() * 2 {
+                return Err(WeightsError::WrongValueCount(
+                    name.to_string(),
+                    weight_values.len(),
+                ));
+            }
+            for (score, pair) in scores.iter_mut().zip(weight_values.chunks(2)) {
+                *score = Score::new(pair[0], pair[1]);
+            }
+        }
+        Ok(weights)
+    }
+
+    // Every weight by name, followed by a middlegame and an endgame value for each entry
+    pub fn to_text(&self) -> String {
+        let mut text = String::from(
+            "# Each weight is followed by a middlegame and an endgame value per entry\n",
+        );
+        for (name, scores) in self.clone().get_named_weights() {
+            text.push_str(&name);
+            text.push('\n');
+            // Eight to a line, so piece-square tables keep the shape of the board
+            for row in scores.chunks(8) {
+                let row: Vec<String> = row
+                    .iter()
+                    .map(|score| format!("{} {}", score.mg, score.eg))
+                    .collect();
+                text.push_str(&row.join("  "));
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    // Each weight with its name in the text format, in the order to_text writes them
+    fn get_named_weights(&mut self) -> Vec<(String, &mut [Score])> {
+        let EvalWeights {
+            material,
+            psqt,
+            mobility,
+            doubled_pawn,
+            isolated_pawn,
+            passed_pawn,
+            pawn_shield,
+            king_zone_attack,
+        } = self;
+        let mut named: Vec<(String, &mut [Score])> = vec![("material".to_string(), material)];
+        for (name, table) in PIECE_NAMES.iter().zip(psqt.iter_mut()) {
+            named.push((format!("psqt_{name}"), table));
+        }
+        named.extend([
+            ("mobility".to_string(), &mut mobility[..]),
+            (
+                "doubled_pawn".to_string(),
+                std::slice::from_mut(doubled_pawn),
+            ),
+            (
+                "isolated_pawn".to_string(),
+                std::slice::from_mut(isolated_pawn),
+            ),
+            ("passed_pawn".to_string(), &mut passed_pawn[..]),
+            ("pawn_shield".to_string(), std::slice::from_mut(pawn_shield)),
+            (
+                "king_zone_attack".to_string(),
+                std::slice::from_mut(king_zone_attack),
+            ),
+        ]);
+        named
+    }
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        EvalWeights::DEFAULT
+    }
+}
+
+// The position in centipawns for the side to move, with the default weights
+pub fn evaluate(chess: &Chess) -> i32 {
+    evaluate_with(chess, &EvalWeights::DEFAULT)
+}
+
+pub fn evaluate_with(chess: &Chess, weights: &EvalWeights) -> i32 {
+    let score =
+        evaluate_side(chess, weights, Side::White) - evaluate_side(chess, weights, Side::Black);
+
+    let phase_pieces = |piece_type| {
+        (chess.get_pieces(Side::White, piece_type) | chess.get_pieces(Side::Black, piece_type))
+            .count() as i32
+    };
+    let phase = (phase_pieces(PieceType::Knight)
+        + phase_pieces(PieceType::Bishop)
+        + 2 * phase_pieces(PieceType::Rook)
+        + 4 * phase_pieces(PieceType::Queen))
+    .min(MAX_PHASE);
+    let score = (score.mg * phase + score.eg * (MAX_PHASE - phase)) / MAX_PHASE;
+
+    match chess.turn {
+        Side::White => score,
+        Side::Black => -score,
+    }
+}
+
+fn evaluate_side(chess: &Chess, weights: &EvalWeights, side: Side) -> Score {
+    let mut score = Score::default();
+    for piece_type in PieceType::ALL {
+        let i = piece_type as usize;
+        for square in chess.get_pieces(side, piece_type) {
+            score += weights.material[i] + weights.psqt[i][get_table_index(side, square)];
+            // Castling needs attack checks and the king has no mobility weight anyway
+            if piece_type != PieceType::King {
+                score += weights.mobility[i] * chess.get_pseudo_moves(square).count() as i32;
+            }
+        }
+    }
+    score + get_pawn_structure(chess, weights, side) + get_king_safety(chess, weights, side)
+}
+
+// Where a square is in a piece-square table, as seen from the given side
+fn get_table_index(side: Side, square: Square) -> usize {
+    let row = match side {
+        Side::White => 7 - square.rank(),
+        Side::Black => square.rank(),
+    };
+    row as usize * 8 + square.file() as usize
+}
+
+// Squares on the ranks in front of the square, as seen from the given side
+fn get_ranks_ahead(side: Side, square: Square) -> Bitboard {
+    match side {
+        Side::White => Bitboard(!0u64 << 8 << (8 * square.rank())),
+        Side::Black => Bitboard((1u64 << (8 * square.rank())) - 1),
+    }
+}
+
+fn get_adjacent_files(file: u8) -> Bitboard {
+    let mut files = Bitboard::EMPTY;
+    if file > 0 {
+        files |= Bitboard::FILES[file as usize - 1];
+    }
+    if file < 7 {
+        files |= Bitboard::FILES[file as usize + 1];
+    }
+    files
+}
+
+fn get_pawn_structure(chess: &Chess, weights: &EvalWeights, side: Side) -> Score {
+    let pawns = chess.get_pieces(side, PieceType::Pawn);
+    let enemy_pawns = chess.get_pieces(side.get_opposite(), PieceType::Pawn);
+    let mut score = Score::default();
+
+    for file in Bitboard::FILES {
+        let count = (pawns & file).count() as i32;
+        if count > 1 {
+            score += weights.doubled_pawn * (count - 1);
+        }
+    }
+
+    for square in pawns {
+        let adjacent_files = get_adjacent_files(square.file());
+        if (pawns & adjacent_files).is_empty() {
+            score += weights.isolated_pawn;
+        }
+        // No enemy pawn can block or capture it on its way to promotion
+        let own_file = Bitboard::FILES[square.file() as usize];
+        if (enemy_pawns & (own_file | adjacent_files) & get_ranks_ahead(side, square)).is_empty() {
+            let rank = match side {
+                Side::White => square.rank(),
+                Side::Black => 7 - square.rank(),
+            };
+            score += weights.passed_pawn[rank as usize];
+        }
+    }
+    score
+}
+
+fn get_king_safety(chess: &Chess, weights: &EvalWeights, side: Side) -> Score {
+    let king = match chess.get_pieces(side, PieceType::King).lsb() {
+        Some(king) => king,
+        None => return Score::default(),
+    };
+    let forward = |bitboard: Bitboard| match side {
+        Side::White => bitboard.north(),
+        Side::Black => bitboard.south(),
+    };
+    let king_files = king.bit() | king.bit().east() | king.bit().west();
+    let shield_squares = forward(king_files) | forward(forward(king_files));
+    let shield = (chess.get_pieces(side, PieceType::Pawn) & shield_squares).count() as i32;
+
+    let zone = attacks::king_attacks(king);
+    let enemy = side.get_opposite();
+    let occupied = chess.get_occupied();
+    let mut zone_attacks = 0;
+    for piece_type in PieceType::ALL {
+        for square in chess.get_pieces(enemy, piece_type) {
+            let attacked = match piece_type {
+                PieceType::Pawn => attacks::pawn_attacks(enemy, square),
+                PieceType::Bishop => attacks::bishop_attacks(square, occupied),
+                PieceType::Knight => attacks::knight_attacks(square),
+                PieceType::Rook => attacks::rook_attacks(square, occupied),
+                PieceType::Queen => attacks::queen_attacks(square, occupied),
+                PieceType::King => continue,
+            };
+            zone_attacks += (attacked & zone).count() as i32;
+        }
+    }
+    weights.pawn_shield * shield + weights.king_zone_attack * zone_attacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The same position with the colours swapped and the board flipped
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |text: &str| -> String {
+            text.chars()
+                .map(|c| {
+                    if c.is_ascii_uppercase() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c.to_ascii_uppercase()
+                    }
+                })
+                .collect()
+        };
+        let board: Vec<&str> = fields[0].split('/').rev().collect();
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+        let en_passant = match fields[3] {
+            "-" => "-".to_string(),
+            square => {
+                let rank = if &square[1..] == "3" { "6" } else { "3" };
+                format!("{}{rank}", &square[..1])
+            }
+        };
+        format!(
+            "{} {turn} {} {en_passant} {} {}",
+            swap_case(&board.join("/")),
+            swap_case(fields[2]),
+            fields[4],
+            fields[5]
+        )
+    }
+
+    #[test]
+    fn colours_are_symmetric() {
+        assert_eq!(evaluate(&Chess::new()), 0);
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ] {
+            let chess = Chess::from_fen(fen).unwrap();
+            let mirrored = Chess::from_fen(&mirror(fen)).unwrap();
+            assert_eq!(evaluate(&chess), evaluate(&mirrored), "{fen}");
+        }
+    }
+
+    #[test]
+    fn scores_pawn_structure() {
+        let weights = EvalWeights::DEFAULT;
+        let structure = |fen| {
+            let chess = Chess::from_fen(fen).unwrap();
+            get_pawn_structure(&chess, &weights, Side::White)
+        };
+        // Doubled and isolated on the c file, the b pawn stops them from being passed
+        assert_eq!(
+            structure("4k3/1p6/8/8/2P5/2P5/8/4K3 w - - 0 1"),
+            weights.doubled_pawn + weights.isolated_pawn * 2
+        );
+        // Passed on the sixth rank and supported by the g pawn
+        assert_eq!(
+            structure("4k3/8/7P/6P1/8/8/8/4K3 w - - 0 1"),
+            weights.passed_pawn[5] + weights.passed_pawn[4]
+        );
+    }
+
+    #[test]
+    fn reads_and_writes_weights_as_text() {
+        let text = EvalWeights::DEFAULT.to_text();
+        assert!(text.contains("\npsqt_knight\n-50 -50  -40 -40  -30 -30"));
+        assert_eq!(EvalWeights::from_text(&text), Ok(EvalWeights::DEFAULT));
+
+        // Anything left out keeps its default
+        let weights = EvalWeights::from_text(
+            "doubled_pawn -30 -40 # harsher\nmobility\n0 0 1 1 2 2 3 3 4 4 5 5",
+        )
+        .unwrap();
+        assert_eq!(weights.doubled_pawn, Score::new(-30, -40));
+        assert_eq!(weights.mobility[5], Score::new(5, 5));
+        assert_eq!(weights.material, EvalWeights::DEFAULT.material);
+
+        for (text, err) in [
+            ("tempo 10 0", WeightsError::UnknownWeight("tempo".into())),
+            (
+                "pawn_shield 10",
+                WeightsError::WrongValueCount("pawn_shield".into(), 1),
+            ),
+            ("pawn_shield 10 1x", WeightsError::InvalidValue("1x".into())),
+            ("10 0", WeightsError::InvalidValue("10".into())),
+        ] {
+            assert_eq!(EvalWeights::from_text(text), Err(err), "{text}");
+        }
+    }
+
+    #[test]
+    fn prefers_the_better_position() {
+        let score = |fen| evaluate(&Chess::from_fen(fen).unwrap());
+        // An extra queen, seen from both sides
+        assert!(score("4k3/8/8/8/8/8/8/3QK3 w - - 0 1") > 800);
+        assert!(score("4k3/8/8/8/8/8/8/3QK3 b - - 0 1") < -800);
+        // A king sheltered by its pawns against one left in the open
+        assert!(
+            score("rq4k1/5ppp/8/8/8/8/5PPP/RQ4K1 w - - 0 1")
+                > score("rq4k1/5ppp/8/8/8/8/5PPP/RQ1K4 w - - 0 1")
+        );
+    }
+}
@@ -0,0 +1,33 @@
+//! Chess rules engine: move generation, game status, FEN and PGN, and an evaluation and
+//! alpha-beta search to play against, without any GUI dependencies.
+//!
+//! The macroquad GUI is behind the default `gui` feature, depend on this crate with
+//! `default-features = false` to use the rules headless.
+
+pub mod attacks;
+mod bitboard;
+mod chess;
+mod eval;
+mod ordering;
+mod pgn;
+mod san;
+mod search;
+mod see;
+mod square;
+mod tt;
+mod uci;
+mod zobrist;
+
+pub use bitboard::{Bitboard, BitboardIter};
+pub use chess::{
+    Castling, Chess, DrawReason, EnPassant, FenError, GameStatus, Move, MoveError, MoveFlag,
+    MoveOutcome, Piece, PieceType, PromotionError, Side, UndoInfo, STARTING_FEN,
+};
+pub use eval::{evaluate, evaluate_with, EvalWeights, Score, WeightsError};
+pub use pgn::{Game, GameNode, PgnError, PgnReader};
+pub use san::SanError;
+pub use search::{search, search_with, SearchLimits, SearchResult, MATE_SCORE};
+pub use see::see;
+pub use square::{ParseSquareError, Square};
+pub use tt::{Bound, TableEntry, TranspositionTable};
+pub use uci::UciError;
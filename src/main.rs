@@ -1,12 +1,24 @@
-mod chess;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use chess::{get_nth_bit, Chess, PieceType};
+use chess::{
+    search_with, see, Bitboard, Chess, DrawReason, EvalWeights, Game, GameStatus, Move, MoveError,
+    MoveOutcome, PgnError, PgnReader, Piece, PieceType, SearchLimits, SearchResult, Side, Square,
+    TranspositionTable, UndoInfo,
+};
+use macroquad::audio::{load_sound, play_sound_once, Sound};
 use macroquad::prelude::*;
 
 const W: f32 = 800.0;
 const H: f32 = 800.0;
 const SQ: f32 = W / 8.0;
+// How long the computer thinks about each move
+const ENGINE_TIME: Duration = Duration::from_secs(1);
+// Megabytes of transposition table, kept from one computer move to the next
+const ENGINE_TABLE_SIZE: usize = 64;
 
 fn window_conf() -> Conf {
     Conf {
@@ -21,9 +33,10 @@ fn window_conf() -> Conf {
 fn draw_board(
     chess: &Chess,
     piece_textures: &HashMap<char, Texture2D>,
-    selected: Option<u8>,
-    legal_moves: u64,
-    in_check: Option<usize>,
+    selected: Option<Square>,
+    legal_moves: Bitboard,
+    losing_captures: Bitboard,
+    in_check: Option<Side>,
 ) {
     let colors = [Color::from_hex(0xf3f3f4), Color::from_hex(0x6a9b41)];
     let texture_params = DrawTextureParams {
@@ -32,41 +45,224 @@ fn draw_board(
     };
 
     for i in 0..64 {
-        let x = (i % 8) as f32;
-        let y = (i / 8) as f32;
-        draw_rectangle(x * SQ, y * SQ, SQ, SQ, colors[(x + y) as usize % 2]);
-
-        let piece = chess.get_piece_at(63 - i);
-        if piece.is_some() {
-            let piece = piece.unwrap();
-            if in_check.is_some() {
-                if piece.side == in_check.unwrap() && piece.piece_type == PieceType::KING {
-                    draw_rectangle(x * SQ, y * SQ, SQ, SQ, RED);
-                }
+        let square = Square::new(i).unwrap();
+        let (x, y) = display_position(square);
+        draw_rectangle(x, y, SQ, SQ, colors[square.is_dark() as usize]);
+
+        if let Some(piece) = chess.get_piece_at(square) {
+            if in_check == Some(piece.side) && piece.piece_type == PieceType::King {
+                draw_rectangle(x, y, SQ, SQ, RED);
             }
             draw_texture_ex(
                 &piece_textures[&piece.get_char()],
-                x * SQ,
-                y * SQ,
+                x,
+                y,
                 WHITE,
                 texture_params.clone(),
             );
         }
-        if selected.is_some() && i == selected.unwrap() {
-            draw_rectangle_lines(x * SQ, y * SQ, SQ, SQ, 10.0, BLUE);
+        if selected == Some(square) {
+            draw_rectangle_lines(x, y, SQ, SQ, 10.0, BLUE);
         }
 
-        if get_nth_bit(legal_moves, 63 - i) == 1 {
-            draw_circle(
-                x * SQ + SQ / 2.0,
-                y * SQ + SQ / 2.0,
-                SQ / 4.0,
-                Color::from_rgba(27, 27, 27, 100),
-            );
+        if legal_moves.contains(square) {
+            // Captures that lose material in the exchange that follows are marked in red
+            let color = if losing_captures.contains(square) {
+                Color::from_rgba(200, 30, 30, 140)
+            } else {
+                Color::from_rgba(27, 27, 27, 100)
+            };
+            draw_circle(x + SQ / 2.0, y + SQ / 2.0, SQ / 4.0, color);
         }
     }
 }
 
+// Top left corner of the square on screen, white is at the bottom
+fn display_position(square: Square) -> (f32, f32) {
+    (square.file() as f32 * SQ, (7 - square.rank()) as f32 * SQ)
+}
+
+// Square under the mouse, if it is over the board
+fn square_at(x: f32, y: f32) -> Option<Square> {
+    if (0.0..8.0 * SQ).contains(&x) && (0.0..8.0 * SQ).contains(&y) {
+        Square::from_file_rank((x / SQ) as u8, 7 - (y / SQ) as u8)
+    } else {
+        None
+    }
+}
+
+// Squares of the promotion picker, stacked from the promotion square towards the centre
+fn promotion_choices(to: Square) -> [(Square, PieceType); 4] {
+    let step = if to.rank() == 7 { -1 } else { 1 };
+    let mut choices = [(to, PieceType::Queen); 4];
+    for (n, piece_type) in PieceType::PROMOTIONS.into_iter().enumerate() {
+        choices[n] = (to.offset(0, step * n as i8).unwrap(), piece_type);
+    }
+    choices
+}
+
+fn draw_promotion_picker(piece_textures: &HashMap<char, Texture2D>, to: Square, side: Side) {
+    let texture_params = DrawTextureParams {
+        dest_size: Some(Vec2::new(SQ, SQ)),
+        ..Default::default()
+    };
+    for (square, piece_type) in promotion_choices(to) {
+        let (x, y) = display_position(square);
+        draw_rectangle(x, y, SQ, SQ, LIGHTGRAY);
+        draw_rectangle_lines(x, y, SQ, SQ, 4.0, DARKGRAY);
+        draw_texture_ex(
+            &piece_textures[&Piece::new(side, piece_type).get_char()],
+            x,
+            y,
+            WHITE,
+            texture_params.clone(),
+        );
+    }
+}
+
+// A banner along the bottom of the board, for why a move was refused or where a game was saved
+fn draw_message(text: &str, color: Color) {
+    draw_rectangle(0.0, H - SQ / 2.0, W, SQ / 2.0, color);
+    let size = measure_text(text, None, 30, 1.0);
+    draw_text(
+        text,
+        (W - size.width) / 2.0,
+        H - (SQ / 2.0 - size.height) / 2.0,
+        30.0,
+        WHITE,
+    );
+}
+
+fn draw_status(status: GameStatus) {
+    let text = match status {
+        GameStatus::Ongoing => return,
+        GameStatus::Checkmate(Side::White) => "Checkmate, white wins",
+        GameStatus::Checkmate(_) => "Checkmate, black wins",
+        GameStatus::Stalemate => "Stalemate",
+        GameStatus::Draw(DrawReason::FiftyMoveRule) => "Draw by the fifty-move rule",
+        GameStatus::Draw(DrawReason::SeventyFiveMoveRule) => "Draw by the seventy-five-move rule",
+        GameStatus::Draw(DrawReason::ThreefoldRepetition) => "Draw by threefold repetition",
+        GameStatus::Draw(DrawReason::FivefoldRepetition) => "Draw by fivefold repetition",
+        GameStatus::Draw(DrawReason::InsufficientMaterial) => "Draw by insufficient material",
+    };
+    draw_rectangle(
+        0.0,
+        H / 2.0 - SQ / 2.0,
+        W,
+        SQ,
+        Color::from_rgba(27, 27, 27, 200),
+    );
+    let size = measure_text(text, None, 40, 1.0);
+    draw_text(
+        text,
+        (W - size.width) / 2.0,
+        (H + size.height) / 2.0,
+        40.0,
+        WHITE,
+    );
+}
+
+// Targets the piece can capture on but would come out behind in the exchange
+fn get_losing_captures(chess: &mut Chess, from: Square, legal_moves: Bitboard) -> Bitboard {
+    legal_moves
+        .into_iter()
+        .filter(|to| {
+            let promotion = chess.is_promotion(from, *to).then_some(PieceType::Queen);
+            chess
+                .find_legal_move(from, *to, promotion)
+                .is_ok_and(|mv| mv.is_capture() && see(chess, mv) < 0)
+        })
+        .collect()
+}
+
+// Starting position and main line of the first game in a PGN file
+fn load_game(path: &str) -> Result<(Chess, Vec<Move>), PgnError> {
+    let file = File::open(path).map_err(PgnError::Io)?;
+    let game = PgnReader::new(BufReader::new(file))
+        .next()
+        .unwrap_or(Err(PgnError::NoGame))?;
+    let start = game.get_starting_position().map_err(PgnError::InvalidFen)?;
+    Ok((start, game.get_main_line()))
+}
+
+// Writes the game to a new file in the working directory, named after when it was saved
+fn save_game(game: &Game) -> std::io::Result<String> {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let path = format!("game-{seconds}.pgn");
+    std::fs::write(&path, game.to_pgn())?;
+    Ok(path)
+}
+
+// Weights for the engine in the format EvalWeights::to_text writes
+fn load_weights(path: &str) -> Result<EvalWeights, Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(EvalWeights::from_text(&text)?)
+}
+
+fn get_result(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Ongoing => "*",
+        GameStatus::Checkmate(Side::White) => "1-0",
+        GameStatus::Checkmate(Side::Black) => "0-1",
+        GameStatus::Stalemate | GameStatus::Draw(_) => "1/2-1/2",
+    }
+}
+
+struct Sounds {
+    muted: bool,
+    move_self: Sound,
+    move_opponent: Sound,
+    capture: Sound,
+    castle: Sound,
+    check: Sound,
+    promote: Sound,
+    illegal: Sound,
+    game_start: Sound,
+}
+
+impl Sounds {
+    async fn load() -> Sounds {
+        Sounds {
+            muted: false,
+            move_self: load_sound("audio/move-self.ogg").await.unwrap(),
+            move_opponent: load_sound("audio/move-opponent.ogg").await.unwrap(),
+            capture: load_sound("audio/capture.ogg").await.unwrap(),
+            castle: load_sound("audio/castle.ogg").await.unwrap(),
+            check: load_sound("audio/move-check.ogg").await.unwrap(),
+            promote: load_sound("audio/promote.ogg").await.unwrap(),
+            illegal: load_sound("audio/illegal.ogg").await.unwrap(),
+            game_start: load_sound("audio/game-start.ogg").await.unwrap(),
+        }
+    }
+
+    fn play(&self, sound: &Sound) {
+        if !self.muted {
+            play_sound_once(sound);
+        }
+    }
+
+    // One sound per move, a check is the most important thing to hear about. White's moves
+    // sound like your own and black's like the opponent's, as the board is seen from white.
+    fn play_move(&self, outcome: &MoveOutcome, side: Side) {
+        let sound = if outcome.is_check {
+            &self.check
+        } else if outcome.promotion.is_some() {
+            &self.promote
+        } else if outcome.mv.is_castle() {
+            &self.castle
+        } else if outcome.captured.is_some() {
+            &self.capture
+        } else if side == Side::White {
+            &self.move_self
+        } else {
+            &self.move_opponent
+        };
+        self.play(sound);
+    }
+}
+
 async fn load_textures() -> HashMap<char, Texture2D> {
     let mut textures = HashMap::new();
     textures.insert('P', load_texture("images/P.png").await.unwrap());
@@ -82,45 +278,248 @@ async fn load_textures() -> HashMap<char, Texture2D> {
     textures.insert('r', load_texture("images/r.png").await.unwrap());
     textures.insert('q', load_texture("images/q.png").await.unwrap());
     textures.insert('k', load_texture("images/k.png").await.unwrap());
-    return textures;
+    textures
 }
 
 #[macroquad::main(window_conf)]
 async fn main() {
-    let mut chess = Chess::new();
+    // The engine's evaluation weights can be loaded from a file with --weights
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let weights = match args.iter().position(|arg| arg == "--weights") {
+        Some(index) if index + 1 < args.len() => {
+            let path = args.remove(index + 1);
+            args.remove(index);
+            load_weights(&path).unwrap_or_else(|err| {
+                eprintln!("Invalid weights: {err}");
+                std::process::exit(1);
+            })
+        }
+        Some(_) => {
+            eprintln!("--weights needs a file");
+            std::process::exit(1);
+        }
+        None => EvalWeights::DEFAULT,
+    };
+
+    // An optional argument sets up the starting position, either a FEN or a PGN file whose
+    // moves are replayed
+    let (mut chess, moves) = match args.into_iter().next() {
+        Some(path) if path.ends_with(".pgn") => load_game(&path).unwrap_or_else(|err| {
+            eprintln!("Invalid PGN: {err}");
+            std::process::exit(1);
+        }),
+        Some(fen) => {
+            let chess = Chess::from_fen(&fen).unwrap_or_else(|err| {
+                eprintln!("Invalid FEN: {err}");
+                std::process::exit(1);
+            });
+            (chess, Vec::new())
+        }
+        None => (Chess::new(), Vec::new()),
+    };
+    let start = chess.clone();
+    let mut played: Vec<(Move, UndoInfo)> = Vec::new();
+    for mv in moves {
+        played.push((mv, chess.make_move(mv)));
+    }
 
     let piece_textures = load_textures().await;
+    let mut sounds = Sounds::load().await;
+    sounds.play(&sounds.game_start);
     let mut selected = None;
-    let mut legal_moves = 0;
-    let mut in_check = None;
+    let mut legal_moves = Bitboard::EMPTY;
+    let mut losing_captures = Bitboard::EMPTY;
+    let mut in_check = if chess.is_in_check(chess.turn) {
+        Some(chess.turn)
+    } else {
+        None
+    };
+    let mut promotion: Option<(Square, Square)> = None;
+    let mut status = chess.status();
+    let mut error: Option<MoveError> = None;
+    let mut notice: Option<String> = None;
+    let mut engine_side: Option<Side> = None;
+    let mut thinking: Option<JoinHandle<(SearchResult, TranspositionTable)>> = None;
+    let mut table = Some(TranspositionTable::new(ENGINE_TABLE_SIZE));
 
     loop {
         clear_background(BLACK);
-        draw_board(&chess, &piece_textures, selected, legal_moves, in_check);
-
-        if is_mouse_button_pressed(MouseButton::Left) {
-            let (mut x, mut y) = mouse_position();
-            x /= SQ;
-            y /= SQ;
-            let i = x as u8 + y as u8 * 8;
-
-            if selected.is_none() {
-                if 0.0 < x && x < 8.0 && 0.0 < y && y < 8.0 {
-                    selected = Some(i);
-                    legal_moves = chess.legal_moves(63 - selected.unwrap(), Some(chess.turn));
+        draw_board(
+            &chess,
+            &piece_textures,
+            selected,
+            legal_moves,
+            losing_captures,
+            in_check,
+        );
+        if let Some((_, to)) = promotion {
+            draw_promotion_picker(&piece_textures, to, chess.turn);
+        }
+        draw_status(status);
+        if let Some(err) = error {
+            draw_message(&err.to_string(), Color::from_rgba(120, 20, 20, 200));
+        } else if let Some(notice) = &notice {
+            draw_message(notice, Color::from_rgba(27, 27, 27, 200));
+        }
+
+        let mut position_changed = false;
+
+        if is_key_pressed(KeyCode::M) {
+            sounds.muted = !sounds.muted;
+        }
+
+        if is_key_pressed(KeyCode::F) {
+            println!("{}", chess.to_fen());
+        }
+
+        if is_key_pressed(KeyCode::P) {
+            let moves: Vec<Move> = played.iter().map(|(mv, _)| *mv).collect();
+            let mut game = Game::from_moves(&start, &moves);
+            game.set_tag("Result", get_result(status));
+            let text = match save_game(&game) {
+                Ok(path) => format!("Saved the game to {path}"),
+                Err(err) => format!("Could not save the game: {err}"),
+            };
+            println!("{text}");
+            notice = Some(text);
+        }
+
+        // The computer takes over the side to move, pressing again hands it back
+        if is_key_pressed(KeyCode::E) {
+            engine_side = match engine_side {
+                Some(_) => None,
+                None => Some(chess.turn),
+            };
+            thinking = None;
+        }
+
+        // Takes back the last move, even after the game has ended. The computer stops playing
+        // so the move can be replayed by hand.
+        if is_key_pressed(KeyCode::U) {
+            engine_side = None;
+            thinking = None;
+            if let Some((mv, undo)) = played.pop() {
+                chess.unmake_move(mv, undo);
+                position_changed = true;
+                error = None;
+                notice = None;
+            }
+        }
+
+        // Threefold repetition and the fifty-move rule only end the game when claimed
+        if status == GameStatus::Ongoing && is_key_pressed(KeyCode::D) {
+            if let Some(reason) = chess.can_claim_draw() {
+                status = GameStatus::Draw(reason);
+            }
+        }
+
+        let engine_to_move = status == GameStatus::Ongoing && engine_side == Some(chess.turn);
+        if engine_to_move && thinking.is_none() {
+            // Searching takes a while, so it runs on its own thread and the window keeps drawing.
+            // The table goes along and comes back with the result, unless the search is
+            // abandoned and a fresh one is needed next time.
+            let position = chess.clone();
+            let weights = weights.clone();
+            let mut table = table
+                .take()
+                .unwrap_or_else(|| TranspositionTable::new(ENGINE_TABLE_SIZE));
+            thinking = Some(thread::spawn(move || {
+                let limits = SearchLimits::time(ENGINE_TIME);
+                let result = search_with(&position, limits, &mut table, &weights);
+                (result, table)
+            }));
+        } else if engine_to_move && thinking.as_ref().is_some_and(|t| t.is_finished()) {
+            let (result, finished_table) = thinking.take().unwrap().join().unwrap();
+            println!(
+                "depth {} score {} nodes {} hashfull {} pv {}",
+                result.depth,
+                result.score,
+                result.nodes,
+                finished_table.hashfull(),
+                result
+                    .pv
+                    .iter()
+                    .map(|mv| mv.to_uci())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+            table = Some(finished_table);
+            if let Some(mv) = result.best_move {
+                let side = chess.turn;
+                let outcome = chess.try_move(mv.from, mv.to, mv.promotion).unwrap();
+                sounds.play_move(&outcome, side);
+                played.push((outcome.mv, outcome.undo));
+                position_changed = true;
+            }
+        }
+
+        if status == GameStatus::Ongoing
+            && !engine_to_move
+            && is_mouse_button_pressed(MouseButton::Left)
+        {
+            let (x, y) = mouse_position();
+            let clicked = square_at(x, y);
+            error = None;
+            notice = None;
+
+            if let Some((from, to)) = promotion {
+                // Clicking outside of the picker cancels the promotion
+                let choice = promotion_choices(to)
+                    .into_iter()
+                    .find(|(square, _)| Some(*square) == clicked);
+                if let Some((_, piece_type)) = choice {
+                    let side = chess.turn;
+                    let outcome = chess.try_move(from, to, Some(piece_type)).unwrap();
+                    sounds.play_move(&outcome, side);
+                    played.push((outcome.mv, outcome.undo));
                 }
-            } else {
-                chess.move_piece(63 - selected.unwrap(), 63 - i as u8);
-                if chess.is_in_check(chess.turn) {
-                    in_check = Some(chess.turn);
-                } else {
-                    in_check = None;
+                position_changed = true;
+            } else if let Some(from) = selected {
+                match clicked {
+                    Some(to) if legal_moves.contains(to) && chess.is_promotion(from, to) => {
+                        promotion = Some((from, to));
+                    }
+                    // Clicking the selected square again just deselects it
+                    Some(to) if to != from => {
+                        let side = chess.turn;
+                        match chess.try_move(from, to, None) {
+                            Ok(outcome) => {
+                                sounds.play_move(&outcome, side);
+                                played.push((outcome.mv, outcome.undo));
+                            }
+                            Err(err) => {
+                                sounds.play(&sounds.illegal);
+                                error = Some(err);
+                            }
+                        }
+                        position_changed = true;
+                    }
+                    _ => position_changed = true,
                 }
-                selected = None;
-                legal_moves = 0;
+            } else if let Some(square) = clicked.filter(|&square| {
+                // Only the side to move can pick up a piece, so empty squares and the opponent's
+                // pieces are ignored instead of becoming an illegal move on the next click
+                chess.get_piece_at(square).map(|piece| piece.side) == Some(chess.turn)
+            }) {
+                selected = Some(square);
+                legal_moves = chess.legal_moves(square, Some(chess.turn));
+                losing_captures = get_losing_captures(&mut chess, square, legal_moves);
             }
         }
 
+        if position_changed {
+            in_check = if chess.is_in_check(chess.turn) {
+                Some(chess.turn)
+            } else {
+                None
+            };
+            status = chess.status();
+            selected = None;
+            legal_moves = Bitboard::EMPTY;
+            losing_captures = Bitboard::EMPTY;
+            promotion = None;
+        }
+
         next_frame().await
     }
 }
@@ -0,0 +1,231 @@
+// Move ordering: alpha-beta cuts off more the sooner it sees the best move. The transposition
+// table move comes first, then captures by most valuable victim and least valuable attacker,
+// then killer moves, then the other quiet moves by history score, and captures that lose
+// material in the exchange last. Quiet moves are only generated when no capture cut off.
+use crate::chess::{Chess, Move, PieceType};
+use crate::search::MAX_PLY;
+use crate::see::{get_see_value, see};
+
+// History scores are halved once one gets this big, so recent cutoffs count for more
+const HISTORY_LIMIT: i32 = 1 << 16;
+
+// What the search learns about quiet moves as it goes
+pub struct Heuristics {
+    // Two quiet moves per ply that caused a cutoff, tried early in sibling positions
+    killers: [[Option<Move>; 2]; MAX_PLY as usize + 1],
+    // How often and how deep a quiet move from one square to another cut off, by side
+    history: [[[i32; 64]; 64]; 2],
+}
+
+impl Heuristics {
+    pub fn new() -> Heuristics {
+        Heuristics {
+            killers: [[None; 2]; MAX_PLY as usize + 1],
+            history: [[[0; 64]; 64]; 2],
+        }
+    }
+
+    pub fn get_killers(&self, ply: u32) -> [Option<Move>; 2] {
+        self.killers[ply as usize]
+    }
+
+    // Called for the move that caused a beta cutoff, before it is taken back
+    pub fn record_cutoff(&mut self, chess: &Chess, mv: Move, ply: u32, depth: u32) {
+        if mv.is_capture() || mv.promotion.is_some() {
+            return;
+        }
+        let killers = &mut self.killers[ply as usize];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+
+        let side = chess.turn as usize;
+        let score = &mut self.history[side][mv.from.index() as usize][mv.to.index() as usize];
+        *score += (depth * depth) as i32;
+        if *score >= HISTORY_LIMIT {
+            for score in self.history.iter_mut().flatten().flatten() {
+                *score /= 2;
+            }
+        }
+    }
+
+    fn get_history(&self, chess: &Chess, mv: Move) -> i32 {
+        self.history[chess.turn as usize][mv.from.index() as usize][mv.to.index() as usize]
+    }
+}
+
+// Most valuable victim, then least valuable attacker. Promotions count as capturing the
+// difference between the new piece and the pawn.
+pub fn get_mvv_lva(chess: &Chess, mv: Move) -> i32 {
+    let victim = if mv.is_en_passant() {
+        Some(PieceType::Pawn)
+    } else {
+        chess.get_piece_at(mv.to).map(|piece| piece.piece_type)
+    };
+    let attacker = chess.get_piece_at(mv.from).map(|piece| piece.piece_type);
+    let promotion = mv.promotion.map_or(0, |piece_type| {
+        get_see_value(piece_type) - get_see_value(PieceType::Pawn)
+    });
+    victim.map_or(0, get_see_value) + promotion - attacker.map_or(0, get_see_value) / 100
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    TableMove,
+    GenerateCaptures,
+    GoodCaptures,
+    GenerateQuiets,
+    Killers,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// Hands out the legal moves of a position one at a time, best guesses first
+pub struct MovePicker {
+    stage: Stage,
+    table_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    moves: Vec<Move>, // The moves of the current stage, best last so they can be popped
+    bad_captures: Vec<Move>,
+}
+
+impl MovePicker {
+    pub fn new(table_move: Option<Move>, killers: [Option<Move>; 2]) -> MovePicker {
+        MovePicker {
+            stage: Stage::TableMove,
+            table_move,
+            killers,
+            moves: Vec::new(),
+            bad_captures: Vec::new(),
+        }
+    }
+
+    pub fn next(&mut self, chess: &mut Chess, heuristics: &Heuristics) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TableMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    // Another position with the same hash could have left a move that isn't
+                    // legal here
+                    if let Some(mv) = self.table_move {
+                        if chess.find_legal_move(mv.from, mv.to, mv.promotion) == Ok(mv) {
+                            return Some(mv);
+                        }
+                        self.table_move = None;
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    let mut captures = Vec::new();
+                    for mv in chess.generate_legal_captures() {
+                        if Some(mv) == self.table_move {
+                            continue;
+                        }
+                        if see(chess, mv) < 0 {
+                            self.bad_captures.push(mv);
+                        } else {
+                            captures.push(mv);
+                        }
+                    }
+                    captures.sort_by_cached_key(|mv| get_mvv_lva(chess, *mv));
+                    self.moves = captures;
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => match self.moves.pop() {
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::GenerateQuiets,
+                },
+                Stage::GenerateQuiets => {
+                    self.moves = chess.generate_legal_quiets();
+                    self.moves.retain(|mv| Some(*mv) != self.table_move);
+                    self.stage = Stage::Killers;
+                }
+                Stage::Killers => {
+                    // Killers come from sibling positions and are only tried if they are legal
+                    // here
+                    for killer in self.killers.iter_mut() {
+                        let index = self.moves.iter().position(|mv| Some(*mv) == *killer);
+                        killer.take();
+                        if let Some(index) = index {
+                            return Some(self.moves.swap_remove(index));
+                        }
+                    }
+                    self.moves
+                        .sort_by_cached_key(|mv| heuristics.get_history(chess, *mv));
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.moves.pop() {
+                    Some(mv) => return Some(mv),
+                    None => {
+                        // The least bad of the losing captures first
+                        self.moves = std::mem::take(&mut self.bad_captures);
+                        self.moves.sort_by_cached_key(|mv| see(chess, *mv));
+                        self.stage = Stage::BadCaptures;
+                    }
+                },
+                Stage::BadCaptures => match self.moves.pop() {
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::Square;
+
+    fn pick_all(chess: &mut Chess, mut picker: MovePicker, heuristics: &Heuristics) -> Vec<Move> {
+        let mut moves = Vec::new();
+        while let Some(mv) = picker.next(chess, heuristics) {
+            moves.push(mv);
+        }
+        moves
+    }
+
+    #[test]
+    fn picks_every_move_once_in_order() {
+        let mut chess =
+            Chess::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let table_move = chess.parse_uci_move("e1g1").unwrap();
+        let killer = chess.parse_uci_move("a2a3").unwrap();
+        let mut heuristics = Heuristics::new();
+        let history_move = chess.parse_uci_move("d5d6").unwrap();
+        heuristics.record_cutoff(&chess, history_move, 30, 4);
+
+        let picker = MovePicker::new(Some(table_move), [Some(killer), None]);
+        let moves = pick_all(&mut chess, picker, &heuristics);
+        let mut expected = chess.generate_legal_moves();
+        assert_eq!(moves.len(), expected.len());
+        let key = |mv: &Move| (mv.from, mv.to, mv.promotion.map(|p| p as u8));
+        let mut sorted = moves.clone();
+        sorted.sort_by_key(key);
+        expected.sort_by_key(key);
+        assert_eq!(sorted, expected);
+
+        assert_eq!(moves[0], table_move);
+        let (good, bad): (Vec<Move>, Vec<Move>) = chess
+            .generate_legal_captures()
+            .into_iter()
+            .partition(|mv| see(&chess, *mv) >= 0);
+        let good_captures = &moves[1..good.len() + 1];
+        assert!(good_captures
+            .windows(2)
+            .all(|pair| get_mvv_lva(&chess, pair[0]) >= get_mvv_lva(&chess, pair[1])));
+        assert!(good_captures.iter().all(|mv| good.contains(mv)));
+        // Bxa6 wins a bishop, Nxd7 loses the knight to any of four recaptures
+        assert_eq!(good_captures[0].to, Square::A6);
+        assert!(bad.iter().any(|mv| mv.to == Square::D7));
+
+        assert_eq!(moves[good.len() + 1], killer);
+        assert_eq!(moves[good.len() + 2], history_move);
+        assert!(moves[moves.len() - bad.len()..]
+            .iter()
+            .all(|mv| bad.contains(mv)));
+    }
+}
@@ -0,0 +1,815 @@
+// Reading and writing games in Portable Game Notation
+use std::io::BufRead;
+
+use crate::chess::{Chess, FenError, Move, Side, STARTING_FEN};
+use crate::san::SanError;
+
+// Tags every PGN game has, written first and in this order
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+const MAX_LINE_LENGTH: usize = 79;
+
+fn get_default_tag_value(name: &str) -> &'static str {
+    match name {
+        "Date" => "????.??.??",
+        "Result" => "*",
+        _ => "?",
+    }
+}
+
+#[derive(Debug)]
+pub enum PgnError {
+    Io(std::io::Error),
+    InvalidTag(String),
+    InvalidFen(FenError),
+    InvalidMove(SanError),
+    UnexpectedToken(String),
+    UnterminatedComment,
+    UnmatchedParenthesis,
+    NoGame,
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PgnError::Io(err) => write!(f, "could not read the PGN: {err}"),
+            PgnError::InvalidTag(tag) => write!(f, "invalid tag pair '{tag}'"),
+            PgnError::InvalidFen(err) => write!(f, "invalid FEN tag: {err}"),
+            PgnError::InvalidMove(err) => write!(f, "{err}"),
+            PgnError::UnexpectedToken(token) => write!(f, "unexpected '{token}' in the movetext"),
+            PgnError::UnterminatedComment => write!(f, "comment is missing its closing brace"),
+            PgnError::UnmatchedParenthesis => write!(f, "variation parentheses don't match"),
+            PgnError::NoGame => write!(f, "there is no game in the PGN"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PgnError::Io(err) => Some(err),
+            PgnError::InvalidFen(err) => Some(err),
+            PgnError::InvalidMove(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// A move in the game tree. The first child continues the line, any others are variations that
+// replace it. The root has no move and its comment comes before the first move.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GameNode {
+    pub mv: Option<Move>,
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    pub children: Vec<GameNode>,
+}
+
+impl GameNode {
+    fn new(mv: Move) -> Self {
+        GameNode {
+            mv: Some(mv),
+            ..Default::default()
+        }
+    }
+
+    fn add_comment(&mut self, comment: &str) {
+        match &mut self.comment {
+            Some(existing) => {
+                existing.push(' ');
+                existing.push_str(comment);
+            }
+            None => self.comment = Some(comment.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub root: GameNode,
+}
+
+impl Game {
+    // An empty game with the seven tag roster filled with unknown values
+    pub fn new() -> Self {
+        let tags = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|name| (name.to_string(), get_default_tag_value(name).to_string()))
+            .collect();
+        Game {
+            tags,
+            root: GameNode::default(),
+        }
+    }
+
+    // A game without variations, such as one played in the GUI
+    pub fn from_moves(start: &Chess, moves: &[Move]) -> Self {
+        let mut game = Game::new();
+        let fen = start.to_fen();
+        if fen != STARTING_FEN {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &fen);
+        }
+        let mut node = &mut game.root;
+        for mv in moves {
+            node.children.push(GameNode::new(*mv));
+            node = &mut node.children[0];
+        }
+        game
+    }
+
+    // Reads the first game of a PGN text
+    pub fn from_pgn(pgn: &str) -> Result<Self, PgnError> {
+        PgnReader::new(pgn.as_bytes())
+            .next()
+            .unwrap_or(Err(PgnError::NoGame))
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn get_result(&self) -> &str {
+        self.get_tag("Result").unwrap_or("*")
+    }
+
+    // The position before the first move, from the FEN tag if there is one
+    pub fn get_starting_position(&self) -> Result<Chess, FenError> {
+        match self.get_tag("FEN") {
+            Some(fen) => Chess::from_fen(fen),
+            None => Ok(Chess::new()),
+        }
+    }
+
+    pub fn get_main_line(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut node = &self.root;
+        while let Some(next) = node.children.first() {
+            moves.extend(next.mv);
+            node = next;
+        }
+        moves
+    }
+
+    // The game as PGN, with the seven tag roster first and the movetext wrapped to fit in 80
+    // columns
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        let roster = SEVEN_TAG_ROSTER.iter().map(|name| {
+            (
+                *name,
+                self.get_tag(name).unwrap_or(get_default_tag_value(name)),
+            )
+        });
+        let others = self
+            .tags
+            .iter()
+            .filter(|(name, _)| !SEVEN_TAG_ROSTER.contains(&name.as_str()))
+            .map(|(name, value)| (name.as_str(), value.as_str()));
+        for (name, value) in roster.chain(others) {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+        }
+        pgn.push('\n');
+
+        let mut movetext = MovetextWriter::default();
+        if let Some(comment) = &self.root.comment {
+            movetext.push_comment(comment);
+        }
+        // An invalid FEN tag leaves nothing to write the moves from
+        if let Ok(mut chess) = self.get_starting_position() {
+            write_line(&mut movetext, &mut chess, &self.root, true);
+        }
+        movetext.push(self.get_result());
+        pgn.push_str(&movetext.finish());
+        pgn
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Writes the line continuing from the node, with the variations of each move after it
+fn write_line(movetext: &mut MovetextWriter, chess: &mut Chess, node: &GameNode, number: bool) {
+    let mut node = node;
+    let mut number = number;
+    while let Some(main) = node.children.first() {
+        let before = chess.clone();
+        number = write_move(movetext, chess, main, number);
+        for variation in &node.children[1..] {
+            let mut chess = before.clone();
+            movetext.open_variation();
+            let variation_number = write_move(movetext, &mut chess, variation, true);
+            write_line(movetext, &mut chess, variation, variation_number);
+            movetext.close_variation();
+            number = true;
+        }
+        node = main;
+    }
+}
+
+// Writes a single move and plays it, returns whether the next move needs its number
+fn write_move(
+    movetext: &mut MovetextWriter,
+    chess: &mut Chess,
+    node: &GameNode,
+    number: bool,
+) -> bool {
+    let mv = match node.mv {
+        Some(mv) => mv,
+        None => return number,
+    };
+    if chess.turn == Side::White {
+        movetext.push(&format!("{}.", chess.fullmove_number));
+    } else if number {
+        movetext.push(&format!("{}...", chess.fullmove_number));
+    }
+    movetext.push(&mv.to_san(chess));
+    chess.make_move(mv);
+    for nag in &node.nags {
+        movetext.push(&format!("${nag}"));
+    }
+    match &node.comment {
+        Some(comment) => {
+            movetext.push_comment(comment);
+            true
+        }
+        None => false,
+    }
+}
+
+// Collects movetext tokens and breaks them into lines
+#[derive(Default)]
+struct MovetextWriter {
+    lines: Vec<String>,
+    line: String,
+    open_parentheses: usize,
+}
+
+impl MovetextWriter {
+    fn push(&mut self, token: &str) {
+        let mut token = token.to_string();
+        // An opening parenthesis sticks to the token after it
+        if self.open_parentheses > 0 {
+            token.insert_str(0, &"(".repeat(self.open_parentheses));
+            self.open_parentheses = 0;
+        }
+        if !self.line.is_empty() && self.line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            self.lines.push(std::mem::take(&mut self.line));
+        }
+        if !self.line.is_empty() {
+            self.line.push(' ');
+        }
+        self.line.push_str(&token);
+    }
+
+    fn push_comment(&mut self, comment: &str) {
+        // A comment can't contain its closing brace, and may be broken over several lines
+        let comment = comment.replace('}', ")");
+        let words: Vec<&str> = comment.split_whitespace().collect();
+        match words.len() {
+            0 => self.push("{}"),
+            1 => self.push(&format!("{{{}}}", words[0])),
+            n => {
+                self.push(&format!("{{{}", words[0]));
+                for word in &words[1..n - 1] {
+                    self.push(word);
+                }
+                self.push(&format!("{}}}", words[n - 1]));
+            }
+        }
+    }
+
+    fn open_variation(&mut self) {
+        self.open_parentheses += 1;
+    }
+
+    // A closing parenthesis sticks to the token before it
+    fn close_variation(&mut self) {
+        if self.line.len() + 1 > MAX_LINE_LENGTH {
+            self.lines.push(std::mem::take(&mut self.line));
+        }
+        self.line.push(')');
+    }
+
+    fn finish(mut self) -> String {
+        self.lines.push(self.line);
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    TagStart,
+    TagEnd,
+    Text(String),
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::TagStart => write!(f, "["),
+            Token::TagEnd => write!(f, "]"),
+            Token::Text(text) => write!(f, "\"{text}\""),
+            Token::Symbol(symbol) | Token::Result(symbol) => write!(f, "{symbol}"),
+            Token::Nag(nag) => write!(f, "${nag}"),
+            Token::Comment(comment) => write!(f, "{{{comment}}}"),
+            Token::VariationStart => write!(f, "("),
+            Token::VariationEnd => write!(f, ")"),
+        }
+    }
+}
+
+// Reads the games of a PGN file one at a time, so whole databases never have to be in memory.
+// A game that fails to parse is returned as an error and reading carries on with the next one.
+pub struct PgnReader<R> {
+    reader: R,
+    line: Vec<char>,
+    position: usize,
+    peeked: Option<Token>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        PgnReader {
+            reader,
+            line: Vec::new(),
+            position: 0,
+            peeked: None,
+        }
+    }
+
+    // Next character, reading another line when the current one runs out
+    fn next_char(&mut self) -> std::io::Result<Option<char>> {
+        while self.position >= self.line.len() {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            // Lines starting with % are escaped and ignored
+            if line.starts_with('%') {
+                continue;
+            }
+            self.line = line.chars().collect();
+            self.position = 0;
+        }
+        self.position += 1;
+        Ok(Some(self.line[self.position - 1]))
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.line.get(self.position).copied()
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, PgnError> {
+        if let Some(token) = self.peeked.take() {
+            return Ok(Some(token));
+        }
+        loop {
+            let character = match self.next_char().map_err(PgnError::Io)? {
+                Some(character) => character,
+                None => return Ok(None),
+            };
+            let token = match character {
+                c if c.is_whitespace() => continue,
+                '[' => Token::TagStart,
+                ']' => Token::TagEnd,
+                '(' => Token::VariationStart,
+                ')' => Token::VariationEnd,
+                '{' => {
+                    let mut comment = String::new();
+                    loop {
+                        match self.next_char().map_err(PgnError::Io)? {
+                            Some('}') => break,
+                            Some(c) => comment.push(c),
+                            None => return Err(PgnError::UnterminatedComment),
+                        }
+                    }
+                    Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+                ';' => {
+                    let comment: String = self.line[self.position..].iter().collect();
+                    self.position = self.line.len();
+                    Token::Comment(comment.trim().to_string())
+                }
+                '"' => {
+                    let mut text = String::new();
+                    loop {
+                        match self.next_char().map_err(PgnError::Io)? {
+                            Some('"') | None => break,
+                            Some('\\') => {
+                                if let Some(c) = self.next_char().map_err(PgnError::Io)? {
+                                    text.push(c);
+                                }
+                            }
+                            Some(c) => text.push(c),
+                        }
+                    }
+                    Token::Text(text)
+                }
+                '$' => {
+                    let mut digits = String::new();
+                    while let Some(c) = self.peek_char().filter(char::is_ascii_digit) {
+                        digits.push(c);
+                        self.position += 1;
+                    }
+                    Token::Nag(
+                        digits
+                            .parse()
+                            .map_err(|_| PgnError::UnexpectedToken(format!("${digits}")))?,
+                    )
+                }
+                _ => {
+                    let mut symbol = character.to_string();
+                    while let Some(c) = self.peek_char() {
+                        if c.is_whitespace() || "[](){};\"$".contains(c) {
+                            break;
+                        }
+                        symbol.push(c);
+                        self.position += 1;
+                    }
+                    match self.split_symbol(symbol) {
+                        Some(token) => token,
+                        None => continue,
+                    }
+                }
+            };
+            return Ok(Some(token));
+        }
+    }
+
+    // Drops move numbers and turns results and !? suffixes into their own tokens
+    fn split_symbol(&mut self, symbol: String) -> Option<Token> {
+        if RESULTS.contains(&symbol.as_str()) {
+            return Some(Token::Result(symbol));
+        }
+        let mut symbol = symbol.as_str();
+        let digits = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+        if digits.len() < symbol.len() && digits.starts_with('.') {
+            symbol = digits.trim_start_matches('.');
+        }
+        let annotation = symbol.trim_start_matches(|c| c != '!' && c != '?');
+        let nag = match annotation {
+            "!" => Some(1),
+            "?" => Some(2),
+            "!!" => Some(3),
+            "??" => Some(4),
+            "!?" => Some(5),
+            "?!" => Some(6),
+            _ => None,
+        };
+        let symbol = &symbol[..symbol.len() - annotation.len()];
+        match (symbol.is_empty(), nag) {
+            (true, None) => None,
+            (true, Some(nag)) => Some(Token::Nag(nag)),
+            (false, nag) => {
+                self.peeked = nag.map(Token::Nag);
+                Some(Token::Symbol(symbol.to_string()))
+            }
+        }
+    }
+
+    // Tokens of the next game, up to its result or the tags of the game after it
+    fn read_game_tokens(&mut self) -> Result<Vec<Token>, PgnError> {
+        let mut tokens = Vec::new();
+        let mut in_tag = false;
+        let mut in_movetext = false;
+        let mut depth = 0;
+        while let Some(token) = self.next_token()? {
+            match token {
+                Token::TagStart if in_movetext => {
+                    self.peeked = Some(token);
+                    break;
+                }
+                Token::TagStart => in_tag = true,
+                Token::TagEnd => in_tag = false,
+                Token::Result(_) if depth == 0 => {
+                    tokens.push(token);
+                    break;
+                }
+                Token::VariationStart => depth += 1,
+                Token::VariationEnd => depth -= 1,
+                _ => {}
+            }
+            // Comments can sit between the tags, only moves and what goes with them start the
+            // movetext
+            in_movetext |= !in_tag
+                && matches!(
+                    token,
+                    Token::Symbol(_) | Token::Nag(_) | Token::VariationStart | Token::VariationEnd
+                );
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tokens = match self.read_game_tokens() {
+            Ok(tokens) if tokens.is_empty() => return None,
+            Ok(tokens) => tokens,
+            Err(err) => {
+                // Whatever is left of the line can't be trusted, start again on the next one
+                self.position = self.line.len();
+                self.peeked = None;
+                return Some(Err(err));
+            }
+        };
+        Some(GameParser { tokens, index: 0 }.parse())
+    }
+}
+
+struct GameParser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl GameParser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next_if_comment(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Comment(comment)) => {
+                let comment = comment.clone();
+                self.index += 1;
+                Some(comment)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse(mut self) -> Result<Game, PgnError> {
+        let mut game = Game::new();
+        // Comments among the tags go with the ones before the first move
+        let mut root = GameNode::default();
+        loop {
+            if let Some(comment) = self.next_if_comment() {
+                root.add_comment(&comment);
+                continue;
+            }
+            if self.peek() != Some(&Token::TagStart) {
+                break;
+            }
+            self.next();
+            match (self.next(), self.next(), self.next()) {
+                (Some(Token::Symbol(name)), Some(Token::Text(value)), Some(Token::TagEnd)) => {
+                    game.set_tag(&name, &value);
+                }
+                tag => {
+                    let tag = [tag.0, tag.1, tag.2].into_iter().flatten();
+                    let tag: Vec<String> = tag.map(|token| token.to_string()).collect();
+                    return Err(PgnError::InvalidTag(tag.join(" ")));
+                }
+            }
+        }
+
+        let mut chess = game.get_starting_position().map_err(PgnError::InvalidFen)?;
+        self.parse_line(&mut chess, &mut root)?;
+        match self.next() {
+            // The Result tag wins if the two disagree
+            Some(Token::Result(result)) if game.get_result() == "*" => {
+                game.set_tag("Result", &result);
+            }
+            Some(Token::Result(_)) | None => {}
+            Some(Token::VariationEnd) => return Err(PgnError::UnmatchedParenthesis),
+            Some(token) => return Err(PgnError::UnexpectedToken(token.to_string())),
+        }
+        game.root = root;
+        Ok(game)
+    }
+
+    // Parses moves continuing from the node until the end of the line, a closing parenthesis or
+    // a result, which are left for the caller
+    fn parse_line(&mut self, chess: &mut Chess, node: &mut GameNode) -> Result<(), PgnError> {
+        let mut node = node;
+        let mut before: Option<Chess> = None;
+        let mut current: Option<usize> = None;
+        // Comments before the first move of a variation belong to that move, not the one the
+        // variation replaces
+        let mut pending: Vec<String> = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(Token::VariationEnd) | Some(Token::Result(_)) => {
+                    for comment in pending {
+                        node.add_comment(&comment);
+                    }
+                    return Ok(());
+                }
+                _ => {}
+            }
+            match self.next().unwrap() {
+                Token::Symbol(san) => {
+                    if let Some(index) = current.take() {
+                        node = &mut node.children[index];
+                    }
+                    let mv = chess.parse_san(&san).map_err(PgnError::InvalidMove)?;
+                    before = Some(chess.clone());
+                    chess.make_move(mv);
+                    let mut child = GameNode::new(mv);
+                    for comment in pending.drain(..) {
+                        child.add_comment(&comment);
+                    }
+                    node.children.push(child);
+                    current = Some(node.children.len() - 1);
+                }
+                Token::Nag(nag) => match current {
+                    Some(index) => node.children[index].nags.push(nag),
+                    None => node.nags.push(nag),
+                },
+                Token::Comment(comment) => match current {
+                    Some(index) => node.children[index].add_comment(&comment),
+                    None => pending.push(comment),
+                },
+                Token::VariationStart => {
+                    // A variation replaces the move just played
+                    let mut alternative = match &before {
+                        Some(before) if current.is_some() => before.clone(),
+                        _ => return Err(PgnError::UnexpectedToken("(".to_string())),
+                    };
+                    self.parse_line(&mut alternative, node)?;
+                    if self.next() != Some(Token::VariationEnd) {
+                        return Err(PgnError::UnmatchedParenthesis);
+                    }
+                }
+                token => return Err(PgnError::UnexpectedToken(token.to_string())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::Square;
+
+    const GAME: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[ECO "C95"]
+
+{Opening comment} 1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.}
+3... a6 $1 (3... Nf6 4. O-O (4. d3 d6) 4... Nxe4) 4. Ba4 Nf6! 5. O-O Be7 6. Re1 b5
+; a rest of line comment
+7. Bb3 d6 8. c3 O-O 1/2-1/2
+"#;
+
+    #[test]
+    fn reads_tags_comments_and_variations() {
+        let game = Game::from_pgn(GAME).unwrap();
+        assert_eq!(game.get_tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(game.get_tag("ECO"), Some("C95"));
+        assert_eq!(game.get_result(), "1/2-1/2");
+        assert_eq!(game.root.comment.as_deref(), Some("Opening comment"));
+
+        let main_line = game.get_main_line();
+        assert_eq!(main_line.len(), 16);
+        assert_eq!(main_line[15].from, Square::E8);
+        assert!(main_line[15].is_castle());
+
+        let mut node = &game.root;
+        for _ in 0..5 {
+            node = &node.children[0];
+        }
+        assert_eq!(
+            node.comment.as_deref(),
+            Some("This opening is called the Ruy Lopez.")
+        );
+        // 3... a6 $1 and its alternative 3... Nf6, which has 4. d3 nested in it
+        assert_eq!(node.children.len(), 2);
+        assert_eq!(node.children[0].nags, [1]);
+        let variation = &node.children[1];
+        assert_eq!(variation.mv.unwrap().to, Square::F6);
+        assert_eq!(variation.children.len(), 2);
+        assert_eq!(variation.children[1].children[0].mv.unwrap().to, Square::D6);
+        assert_eq!(variation.children[0].children[0].mv.unwrap().to, Square::E4);
+
+        // 4... Nf6! becomes $1 and the ; comment belongs to 6... b5
+        let node = &node.children[0].children[0].children[0];
+        assert_eq!(node.nags, [1]);
+        let node = &node.children[0].children[0].children[0].children[0];
+        assert_eq!(node.comment.as_deref(), Some("a rest of line comment"));
+
+        // A comment opening a variation goes with its first move
+        let game = Game::from_pgn("1. e4 {main} e5 ({alt start} 1... c5) *").unwrap();
+        let node = &game.root.children[0];
+        assert_eq!(node.comment.as_deref(), Some("main"));
+        assert_eq!(node.children[1].comment.as_deref(), Some("alt start"));
+        assert_eq!(Game::from_pgn(&game.to_pgn()).unwrap(), game);
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let game = Game::from_pgn(GAME).unwrap();
+        let pgn = game.to_pgn();
+        assert!(pgn.starts_with("[Event \"F/S Return Match\"]\n"));
+        assert!(pgn.contains("[ECO \"C95\"]\n\n{Opening comment} 1. e4 e5"));
+        assert!(pgn.contains("3... a6 $1 (3... Nf6 4. O-O (4. d3 d6) 4... Nxe4) 4. Ba4"));
+        assert!(pgn.ends_with("O-O 1/2-1/2\n"));
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert_eq!(Game::from_pgn(&pgn).unwrap(), game);
+    }
+
+    #[test]
+    fn streams_several_games() {
+        let pgn = format!(
+            "{GAME}\n[Event \"Broken\"]\n\n1. e4 e5 2. Ke3 *\n\n{}",
+            "[Event \"No result\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n1. e4 Kd7"
+        );
+        let games: Vec<Result<Game, PgnError>> = PgnReader::new(pgn.as_bytes()).collect();
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].as_ref().unwrap().get_main_line().len(), 16);
+        assert!(matches!(games[1], Err(PgnError::InvalidMove(_))));
+        let last = games[2].as_ref().unwrap();
+        assert_eq!(last.get_tag("Event"), Some("No result"));
+        assert_eq!(last.get_result(), "*");
+        assert_eq!(last.get_main_line().len(), 2);
+    }
+
+    #[test]
+    fn reads_comments_between_tags() {
+        let pgn = r#"[Event "A"]
+{between the tags}
+[White "B"]
+
+1. e4 e5 *
+
+[Event "C"]
+
+1. d4 *
+"#;
+        let games: Vec<Game> = PgnReader::new(pgn.as_bytes())
+            .map(|game| game.unwrap())
+            .collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].get_tag("White"), Some("B"));
+        assert_eq!(games[0].root.comment.as_deref(), Some("between the tags"));
+        assert_eq!(games[0].get_main_line().len(), 2);
+        assert_eq!(games[1].get_tag("Event"), Some("C"));
+    }
+
+    #[test]
+    fn saves_played_games() {
+        let mut chess = Chess::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+        let start = chess.clone();
+        let mut moves = Vec::new();
+        for san in ["Kd7", "e4", "Ke6"] {
+            let mv = chess.parse_san(san).unwrap();
+            chess.make_move(mv);
+            moves.push(mv);
+        }
+        // Enough moves to need several lines
+        for _ in 0..30 {
+            match chess.generate_legal_moves().first() {
+                Some(mv) => {
+                    chess.make_move(*mv);
+                    moves.push(*mv);
+                }
+                None => break,
+            }
+        }
+        let game = Game::from_moves(&start, &moves);
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]"));
+        assert!(pgn.contains("\n\n1... Kd7 2. e4 Ke6 3."));
+        assert!(pgn.lines().count() > 10);
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert_eq!(Game::from_pgn(&pgn).unwrap().get_main_line(), moves);
+    }
+}
@@ -0,0 +1,278 @@
+// Standard algebraic notation, as used in PGN and move lists
+use crate::chess::{Chess, Move, Piece, PieceType, Side};
+use crate::square::Square;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    InvalidSyntax(String),
+    IllegalMove(String),
+    AmbiguousMove(String),
+}
+
+impl std::fmt::Display for SanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SanError::InvalidSyntax(san) => {
+                write!(f, "'{san}' is not a move in algebraic notation")
+            }
+            SanError::IllegalMove(san) => write!(f, "'{san}' is not a legal move in this position"),
+            SanError::AmbiguousMove(san) => {
+                write!(f, "'{san}' matches more than one legal move")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+fn get_piece_letter(piece_type: PieceType) -> char {
+    Piece::new(Side::White, piece_type).get_char()
+}
+
+fn parse_piece_letter(letter: char) -> Option<PieceType> {
+    match letter.to_ascii_uppercase() {
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        'K' => Some(PieceType::King),
+        _ => None,
+    }
+}
+
+impl Move {
+    // The move in SAN for the position it is played from, e.g. "Nbd7", "exd6", "e8=Q+" or "O-O"
+    pub fn to_san(&self, chess: &Chess) -> String {
+        let mut chess = chess.clone();
+        let piece_type = match chess.get_piece_at(self.from) {
+            Some(piece) => piece.piece_type,
+            None => return String::new(),
+        };
+
+        let mut san = String::new();
+        if self.is_castle() {
+            san.push_str(if self.to.file() == 6 { "O-O" } else { "O-O-O" });
+        } else {
+            if piece_type == PieceType::Pawn {
+                if self.is_capture() {
+                    san.push((b'a' + self.from.file()) as char);
+                }
+            } else {
+                san.push(get_piece_letter(piece_type));
+
+                // Name the file if that is enough to tell the pieces apart, then the rank,
+                // then both
+                let others: Vec<Square> = chess
+                    .generate_legal_moves()
+                    .into_iter()
+                    .filter(|mv| mv.to == self.to && mv.from != self.from)
+                    .map(|mv| mv.from)
+                    .filter(|from| {
+                        chess.get_piece_at(*from).map(|p| p.piece_type) == Some(piece_type)
+                    })
+                    .collect();
+                if !others.is_empty() {
+                    let from = self.from.to_string();
+                    if others.iter().all(|other| other.file() != self.from.file()) {
+                        san.push_str(&from[..1]);
+                    } else if others.iter().all(|other| other.rank() != self.from.rank()) {
+                        san.push_str(&from[1..]);
+                    } else {
+                        san.push_str(&from);
+                    }
+                }
+            }
+            if self.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&self.to.to_string());
+            if let Some(promotion) = self.promotion {
+                san.push('=');
+                san.push(get_piece_letter(promotion));
+            }
+        }
+
+        chess.make_move(*self);
+        if chess.is_in_check(chess.turn) {
+            san.push(if chess.has_legal_moves() { '+' } else { '#' });
+        }
+        san
+    }
+}
+
+impl Chess {
+    // Finds the legal move written in SAN. Check and annotation marks are optional, and "0-0",
+    // "e8Q", "Pe4" and long forms like "Ng1-f3" are accepted too.
+    pub fn parse_san(&mut self, san: &str) -> Result<Move, SanError> {
+        let invalid = || SanError::InvalidSyntax(san.to_string());
+        let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+
+        let castle_file = match text {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle_file {
+            return self
+                .generate_legal_moves()
+                .into_iter()
+                .find(|mv| mv.is_castle() && mv.to.file() == file)
+                .ok_or(SanError::IllegalMove(san.to_string()));
+        }
+
+        let mut chars: Vec<char> = text.chars().collect();
+
+        // Only an uppercase letter names a piece, a lowercase b is the b file
+        let piece_type = match chars.first() {
+            Some(letter) if letter.is_ascii_uppercase() => {
+                let piece_type = match letter {
+                    'P' => PieceType::Pawn,
+                    _ => parse_piece_letter(*letter).ok_or_else(invalid)?,
+                };
+                chars.remove(0);
+                piece_type
+            }
+            _ => PieceType::Pawn,
+        };
+
+        // A promotion piece follows the target square, with or without "="
+        let mut promotion = None;
+        if chars.len() >= 3 && chars[chars.len() - 1].is_ascii_alphabetic() {
+            promotion = Some(parse_piece_letter(chars.pop().unwrap()).ok_or_else(invalid)?);
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+        }
+
+        if chars.len() < 2 {
+            return Err(invalid());
+        }
+        let to: String = chars.split_off(chars.len() - 2).into_iter().collect();
+        let to: Square = to.parse().map_err(|_| invalid())?;
+
+        // Whatever is left narrows down where the piece comes from
+        let mut from_file = None;
+        let mut from_rank = None;
+        for character in chars {
+            match character {
+                'a'..='h' => from_file = Some(character as u8 - b'a'),
+                '1'..='8' => from_rank = Some(character as u8 - b'1'),
+                'x' | ':' | '-' => {}
+                _ => return Err(invalid()),
+            }
+        }
+
+        let candidates: Vec<Move> = self
+            .generate_legal_moves()
+            .into_iter()
+            .filter(|mv| {
+                mv.to == to
+                    && mv.promotion == promotion
+                    && self.get_piece_at(mv.from).map(|p| p.piece_type) == Some(piece_type)
+                    && from_file.is_none_or(|file| mv.from.file() == file)
+                    && from_rank.is_none_or(|rank| mv.from.rank() == rank)
+            })
+            .collect();
+        match candidates[..] {
+            [mv] => Ok(mv),
+            [] => Err(SanError::IllegalMove(san.to_string())),
+            _ => Err(SanError::AmbiguousMove(san.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san_moves(fen: &str) -> Vec<String> {
+        let mut chess = Chess::from_fen(fen).unwrap();
+        let mut moves: Vec<String> = chess
+            .generate_legal_moves()
+            .iter()
+            .map(|mv| mv.to_san(&chess))
+            .collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn formats_san() {
+        let moves =
+            san_moves("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        for san in [
+            "O-O", "O-O-O", "Nxd7", "Qxf6", "dxe6", "Bxa6", "Rb1", "Kf1", "a4",
+        ] {
+            assert!(moves.contains(&san.to_string()), "{san}");
+        }
+
+        // Knights on b1 and f3 and rooks on a1 and a5 need a file or a rank to tell them apart
+        let moves = san_moves("4k3/8/8/R7/8/5N2/8/RN2K3 w - - 0 1");
+        for san in ["Nbd2", "Nfd2", "R5a3", "R1a3", "Rb5", "Nh4", "R1a2"] {
+            assert!(moves.contains(&san.to_string()), "{san}");
+        }
+        let moves = san_moves("7k/2N5/8/8/8/2N1N3/8/4K3 w - - 0 1");
+        for san in ["Nc3d5", "Ned5", "N7d5", "N3b5", "N7b5", "Ng4"] {
+            assert!(moves.contains(&san.to_string()), "{san}");
+        }
+
+        let moves = san_moves("1r5k/P7/7K/8/8/8/8/8 w - - 0 1");
+        for san in ["a8=Q", "axb8=Q#", "axb8=N", "Kg6"] {
+            assert!(moves.contains(&san.to_string()), "{san}");
+        }
+
+        let moves = san_moves("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert!(moves.contains(&"Ra8#".to_string()));
+    }
+
+    #[test]
+    fn parses_its_own_san() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "7k/2N5/8/8/8/2N1N3/8/4K3 w - - 0 1",
+        ] {
+            let mut chess = Chess::from_fen(fen).unwrap();
+            for mv in chess.generate_legal_moves() {
+                let san = mv.to_san(&chess);
+                assert_eq!(chess.parse_san(&san), Ok(mv), "{san}");
+            }
+        }
+    }
+
+    #[test]
+    fn parses_common_variants() {
+        let mut chess = Chess::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let queen_promotion = chess.parse_san("b8=Q").unwrap();
+        for san in ["b8Q", "b8=Q+", "Pb8=Q", "b7-b8Q", "b8q!?"] {
+            assert_eq!(chess.parse_san(san), Ok(queen_promotion), "{san}");
+        }
+        assert_eq!(chess.parse_san("0-0"), chess.parse_san("O-O"));
+        assert_eq!(chess.parse_san("0-0-0+"), chess.parse_san("O-O-O"));
+        assert_eq!(chess.parse_san("Raxa8"), chess.parse_san("Rxa8+"));
+
+        assert!(matches!(
+            chess.parse_san("b8"),
+            Err(SanError::IllegalMove(_))
+        ));
+        assert!(matches!(
+            chess.parse_san("Zz9"),
+            Err(SanError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            chess.parse_san(""),
+            Err(SanError::InvalidSyntax(_))
+        ));
+
+        let mut chess = Chess::from_fen("7k/2N5/8/8/8/2N1N3/8/4K3 w - - 0 1").unwrap();
+        assert!(matches!(
+            chess.parse_san("Nd5"),
+            Err(SanError::AmbiguousMove(_))
+        ));
+        assert!(matches!(
+            chess.parse_san("Ncd5"),
+            Err(SanError::AmbiguousMove(_))
+        ));
+        assert_eq!(chess.parse_san("Nc3d5"), chess.parse_san("Nc3xd5"));
+    }
+}
@@ -0,0 +1,394 @@
+// Negamax alpha-beta search with iterative deepening and a quiescence search at the leaves
+use std::time::{Duration, Instant};
+
+use crate::chess::{Chess, Move, PieceType};
+use crate::eval::{evaluate_with, EvalWeights};
+use crate::ordering::{get_mvv_lva, Heuristics, MovePicker};
+use crate::see::see;
+use crate::tt::{Bound, TableEntry, TranspositionTable};
+
+// Scores within MAX_PLY of MATE_SCORE are forced mates, MATE_SCORE - n is mate in n plies
+pub const MATE_SCORE: i32 = 30_000;
+const INFINITY: i32 = 32_000;
+pub const MAX_PLY: u32 = 64;
+
+// How often, in nodes, the clock and the node limit are looked at
+const CHECK_INTERVAL: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: u32,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits {
+            depth,
+            ..Default::default()
+        }
+    }
+
+    pub fn time(time: Duration) -> SearchLimits {
+        SearchLimits {
+            time: Some(time),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            depth: MAX_PLY,
+            nodes: None,
+            time: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>, // None when the side to move has no legal move
+    pub score: i32,              // Centipawns for the side to move
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    pub depth: u32, // Deepest iteration that finished
+}
+
+// Size of the table search makes for itself, search_with can be given a longer lived one
+const DEFAULT_TABLE_SIZE: usize = 16;
+
+struct Searcher<'a> {
+    chess: Chess,
+    table: &'a mut TranspositionTable,
+    weights: &'a EvalWeights,
+    heuristics: Heuristics,
+    limits: SearchLimits,
+    start: Instant,
+    nodes: u64,
+    can_stop: bool, // Set once the first iteration is done
+    stopped: bool,
+}
+
+// Searches deeper and deeper until a limit is hit and returns the last iteration that finished.
+// The first iteration always finishes, so there is a move whenever one exists.
+pub fn search(chess: &Chess, limits: SearchLimits) -> SearchResult {
+    search_with(
+        chess,
+        limits,
+        &mut TranspositionTable::new(DEFAULT_TABLE_SIZE),
+        &EvalWeights::DEFAULT,
+    )
+}
+
+// The same with a transposition table that is kept between searches, so what was learned about
+// the game so far carries over to the next move, and evaluation weights of its own
+pub fn search_with(
+    chess: &Chess,
+    limits: SearchLimits,
+    table: &mut TranspositionTable,
+    weights: &EvalWeights,
+) -> SearchResult {
+    table.new_search();
+    let mut searcher = Searcher {
+        chess: chess.clone(),
+        table,
+        weights,
+        heuristics: Heuristics::new(),
+        limits,
+        start: Instant::now(),
+        nodes: 0,
+        can_stop: false,
+        stopped: false,
+    };
+    let mut result = SearchResult {
+        best_move: None,
+        score: 0,
+        pv: Vec::new(),
+        nodes: 0,
+        depth: 0,
+    };
+
+    for depth in 1..=limits.depth.clamp(1, MAX_PLY) {
+        let mut pv = Vec::new();
+        let score = searcher.negamax(depth, 0, -INFINITY, INFINITY, &mut pv);
+        if searcher.stopped {
+            break;
+        }
+        result.best_move = pv.first().copied();
+        result.score = score;
+        result.pv = pv;
+        result.depth = depth;
+        searcher.can_stop = true;
+
+        // Searching deeper can't find a shorter mate
+        if score.abs() >= MATE_SCORE - depth as i32 || searcher.is_out_of_time() {
+            break;
+        }
+    }
+    result.nodes = searcher.nodes;
+    result
+}
+
+impl Searcher<'_> {
+    fn negamax(
+        &mut self,
+        depth: u32,
+        ply: u32,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.count_node();
+        if self.stopped {
+            return 0;
+        }
+        if ply > 0 && self.is_draw() {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(ply, alpha, beta);
+        }
+
+        // An exact score inside the window is searched again anyway, so the principal variation
+        // comes out whole
+        let hash = self.chess.get_hash();
+        let entry = self.table.probe(hash, ply);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let fails_high = entry.bound != Bound::Upper && entry.score >= beta;
+            let fails_low = entry.bound != Bound::Lower && entry.score <= alpha;
+            if fails_high || fails_low {
+                return entry.score;
+            }
+        }
+
+        // The best move found for this position before, often by the previous iteration
+        let table_move = entry.and_then(|entry| entry.best_move);
+        let mut picker = MovePicker::new(table_move, self.heuristics.get_killers(ply));
+
+        let mut bound = Bound::Upper;
+        let mut best_move = None;
+        let mut move_count = 0;
+        let mut child_pv = Vec::new();
+        while let Some(mv) = picker.next(&mut self.chess, &self.heuristics) {
+            move_count += 1;
+            let undo = self.chess.make_move(mv);
+            child_pv.clear();
+            let score = -self.negamax(depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            self.chess.unmake_move(mv, undo);
+            if self.stopped {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                bound = Bound::Exact;
+                best_move = Some(mv);
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+                if alpha >= beta {
+                    bound = Bound::Lower;
+                    self.heuristics.record_cutoff(&self.chess, mv, ply, depth);
+                    break;
+                }
+            }
+        }
+        if move_count == 0 {
+            return if self.chess.is_in_check(self.chess.turn) {
+                -MATE_SCORE + ply as i32
+            } else {
+                0
+            };
+        }
+
+        self.table.store(
+            hash,
+            ply,
+            TableEntry {
+                best_move,
+                score: alpha,
+                depth,
+                bound,
+            },
+        );
+        alpha
+    }
+
+    // Past the horizon only captures and queen promotions are searched, so the evaluation is
+    // never taken halfway through an exchange. The side to move can stand pat on the static
+    // evaluation instead, unless it is in check and has to try every evasion.
+    fn quiescence(&mut self, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        self.count_node();
+        if self.stopped {
+            return 0;
+        }
+        if ply >= MAX_PLY {
+            return evaluate_with(&self.chess, self.weights);
+        }
+
+        let in_check = self.chess.is_in_check(self.chess.turn);
+        if !in_check {
+            let stand_pat = evaluate_with(&self.chess, self.weights);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+        }
+
+        // Not being in check there is no telling a stalemate from a quiet position without
+        // generating every move, which is left to the main search
+        let mut moves = if in_check {
+            let moves = self.chess.generate_legal_moves();
+            if moves.is_empty() {
+                return -MATE_SCORE + ply as i32;
+            }
+            moves
+        } else {
+            // Captures that lose material can't do better than standing pat
+            let mut captures = self.chess.generate_legal_captures();
+            captures.retain(|mv| {
+                mv.promotion
+                    .is_none_or(|piece_type| piece_type == PieceType::Queen)
+                    && see(&self.chess, *mv) >= 0
+            });
+            captures
+        };
+        moves.sort_by_cached_key(|mv| -get_mvv_lva(&self.chess, *mv));
+
+        for mv in moves {
+            let undo = self.chess.make_move(mv);
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.chess.unmake_move(mv, undo);
+            if self.stopped {
+                return 0;
+            }
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        alpha
+    }
+
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        if self.can_stop && self.nodes.is_multiple_of(CHECK_INTERVAL) && self.is_out_of_time() {
+            self.stopped = true;
+        }
+    }
+
+    fn is_out_of_time(&self) -> bool {
+        self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes)
+            || self
+                .limits
+                .time
+                .is_some_and(|time| self.start.elapsed() >= time)
+    }
+
+    // A position that repeats once inside the search is treated as a draw, playing on from it
+    // can't be better than last time
+    fn is_draw(&self) -> bool {
+        self.chess.halfmove_clock >= 100
+            || self.chess.get_repetition_count() >= 2
+            || self.chess.has_insufficient_material()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::Square;
+
+    #[test]
+    fn finds_forced_mates() {
+        let chess = Chess::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = search(&chess, SearchLimits::depth(3));
+        assert_eq!(result.best_move.map(|mv| mv.to), Some(Square::A8));
+        assert_eq!(result.score, MATE_SCORE - 1);
+
+        // 1. Kf7 Kh7 2. Rh1#
+        let chess = Chess::from_fen("7k/8/5K2/8/8/8/8/6R1 w - - 0 1").unwrap();
+        let result = search(&chess, SearchLimits::depth(5));
+        assert_eq!(result.score, MATE_SCORE - 3);
+        assert_eq!(result.pv.len(), 3);
+        assert_eq!(result.best_move.map(|mv| mv.to), Some(Square::F7));
+    }
+
+    #[test]
+    fn sees_recaptures_past_the_horizon() {
+        // Without a quiescence search a one ply search would happily take the guarded pawn
+        let chess = Chess::from_fen("4k3/8/3p4/4p3/8/8/8/K3Q3 w - - 0 1").unwrap();
+        let result = search(&chess, SearchLimits::depth(1));
+        assert_ne!(result.best_move.map(|mv| mv.to), Some(Square::E5));
+    }
+
+    #[test]
+    fn pv_is_a_line_of_legal_moves() {
+        let mut chess =
+            Chess::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let result = search(&chess, SearchLimits::depth(3));
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.best_move, result.pv.first().copied());
+        assert!(result.nodes > 0);
+        for mv in result.pv {
+            assert!(chess.generate_legal_moves().contains(&mv));
+            chess.make_move(mv);
+        }
+    }
+
+    #[test]
+    fn reuses_the_table_between_searches() {
+        let chess =
+            Chess::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let mut table = TranspositionTable::new(1);
+        let weights = EvalWeights::DEFAULT;
+        let first = search_with(&chess, SearchLimits::depth(3), &mut table, &weights);
+        assert!(table.hashfull() > 0);
+        let second = search_with(&chess, SearchLimits::depth(3), &mut table, &weights);
+        assert_eq!(first.score, second.score);
+        assert!(second.nodes < first.nodes);
+    }
+
+    #[test]
+    fn plays_by_the_given_weights() {
+        // Rxd5 wins the queen, unless the queen is worth less than nothing to black
+        let chess = Chess::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let result = search(&chess, SearchLimits::depth(2));
+        assert_eq!(result.best_move.map(|mv| mv.to), Some(Square::D5));
+
+        let weights =
+            EvalWeights::from_text("material 100 100 300 300 300 300 500 500 -2000 -2000 0 0")
+                .unwrap();
+        let mut table = TranspositionTable::new(1);
+        let result = search_with(&chess, SearchLimits::depth(2), &mut table, &weights);
+        assert_ne!(result.best_move.map(|mv| mv.to), Some(Square::D5));
+        assert!(result.score > 1000);
+    }
+
+    #[test]
+    fn stops_at_limits() {
+        let chess = Chess::new();
+        let result = search(
+            &chess,
+            SearchLimits {
+                nodes: Some(5000),
+                ..Default::default()
+            },
+        );
+        assert!(result.best_move.is_some());
+        assert!(result.depth < MAX_PLY);
+
+        // Stalemated, there is nothing to search
+        let chess = Chess::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = search(&chess, SearchLimits::depth(4));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, 0);
+    }
+}
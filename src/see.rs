@@ -0,0 +1,123 @@
+// Static exchange evaluation: what a capture wins or loses once every piece that can recapture on
+// the square has had its turn, cheapest piece first
+use crate::chess::{Chess, Move, PieceType};
+use crate::square::Square;
+
+// Cheapest first, the order pieces join an exchange in
+const EXCHANGE_ORDER: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+pub fn get_see_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight | PieceType::Bishop => 325,
+        PieceType::Rook => 500,
+        PieceType::Queen => 975,
+        PieceType::King => 20_000,
+    }
+}
+
+// Material the side making the move ends up with, in centipawns. Each side may stop recapturing
+// whenever carrying on would lose more, so a capture defended only by a pawn is worth the
+// difference between the two pieces, and a quiet move onto a guarded square is a loss.
+pub fn see(chess: &Chess, mv: Move) -> i32 {
+    let piece = match chess.get_piece_at(mv.from) {
+        Some(piece) => piece,
+        None => return 0,
+    };
+    let mut occupied = chess.get_occupied() & !mv.from.bit();
+    let captured = if mv.is_en_passant() {
+        let pawn = Square::from_file_rank(mv.to.file(), mv.from.rank()).unwrap();
+        occupied &= !pawn.bit();
+        Some(PieceType::Pawn)
+    } else {
+        chess.get_piece_at(mv.to).map(|piece| piece.piece_type)
+    };
+
+    // gains[n] is what the side making the nth capture wins if the exchange stops after it
+    let mut gains = [0; 32];
+    gains[0] = captured.map_or(0, get_see_value);
+    let mut on_square = piece.piece_type;
+    if let Some(promotion) = mv.promotion {
+        gains[0] += get_see_value(promotion) - get_see_value(PieceType::Pawn);
+        on_square = promotion;
+    }
+
+    let mut side = piece.side.get_opposite();
+    let mut depth = 0;
+    while depth + 1 < gains.len() {
+        let attackers = chess.get_attackers(mv.to, side, occupied);
+        let attacker = EXCHANGE_ORDER.into_iter().find_map(|piece_type| {
+            let square = (attackers & chess.get_pieces(side, piece_type)).lsb()?;
+            Some((piece_type, square))
+        });
+        let Some((piece_type, square)) = attacker else {
+            break;
+        };
+        // The king can only recapture when nothing is left to take it back
+        if piece_type == PieceType::King
+            && !chess
+                .get_attackers(mv.to, side.get_opposite(), occupied & !square.bit())
+                .is_empty()
+        {
+            break;
+        }
+
+        depth += 1;
+        gains[depth] = get_see_value(on_square) - gains[depth - 1];
+        on_square = piece_type;
+        occupied &= !square.bit();
+        side = side.get_opposite();
+    }
+
+    // Walk back up, each side only makes its capture if it does not lose by it
+    while depth > 0 {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        depth -= 1;
+    }
+    gains[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see_uci(fen: &str, uci: &str) -> i32 {
+        let mut chess = Chess::from_fen(fen).unwrap();
+        let mv = chess.parse_uci_move(uci).unwrap();
+        see(&chess, mv)
+    }
+
+    #[test]
+    fn plays_out_exchanges() {
+        // An undefended pawn, and one defended by a pawn
+        assert_eq!(
+            see_uci("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            100
+        );
+        assert_eq!(see_uci("4k3/8/3p4/4p3/8/8/8/K3R3 w - - 0 1", "e1e5"), -400);
+        // Knight takes a pawn guarded by a knight and bishop, with more pieces lined up behind
+        assert_eq!(
+            see_uci(
+                "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+                "d3e5"
+            ),
+            -225
+        );
+        // Quiet moves are free unless they step onto a guarded square
+        assert_eq!(see_uci("4k3/8/3p4/8/8/8/8/K3N3 w - - 0 1", "e1c2"), 0);
+        assert_eq!(see_uci("4k3/8/3p4/8/8/5N2/8/K7 w - - 0 1", "f3e5"), -325);
+        // A promotion that gets taken just loses the pawn, winning a rook with it is still good
+        assert_eq!(see_uci("3rk3/2P5/8/8/8/8/8/K7 w - - 0 1", "c7c8q"), -100);
+        assert_eq!(see_uci("3rk3/2P5/8/8/8/8/8/K7 w - - 0 1", "c7d8q"), 400);
+        // The king can take back a piece only when nothing defends it
+        assert_eq!(see_uci("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1", "e2e7"), 0);
+        assert_eq!(see_uci("4k3/4r3/8/8/8/4R3/4R3/4K3 w - - 0 1", "e3e7"), 500);
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bitboard::Bitboard;
+
+// Index 0 is h1 and index 63 is a8, matching the bit order of the bitboards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Square(u8);
+
+impl Square {
+    pub const A1: Square = Square(7);
+    pub const B1: Square = Square(6);
+    pub const C1: Square = Square(5);
+    pub const D1: Square = Square(4);
+    pub const E1: Square = Square(3);
+    pub const F1: Square = Square(2);
+    pub const G1: Square = Square(1);
+    pub const H1: Square = Square(0);
+    pub const A2: Square = Square(15);
+    pub const B2: Square = Square(14);
+    pub const C2: Square = Square(13);
+    pub const D2: Square = Square(12);
+    pub const E2: Square = Square(11);
+    pub const F2: Square = Square(10);
+    pub const G2: Square = Square(9);
+    pub const H2: Square = Square(8);
+    pub const A3: Square = Square(23);
+    pub const B3: Square = Square(22);
+    pub const C3: Square = Square(21);
+    pub const D3: Square = Square(20);
+    pub const E3: Square = Square(19);
+    pub const F3: Square = Square(18);
+    pub const G3: Square = Square(17);
+    pub const H3: Square = Square(16);
+    pub const A4: Square = Square(31);
+    pub const B4: Square = Square(30);
+    pub const C4: Square = Square(29);
+    pub const D4: Square = Square(28);
+    pub const E4: Square = Square(27);
+    pub const F4: Square = Square(26);
+    pub const G4: Square = Square(25);
+    pub const H4: Square = Square(24);
+    pub const A5: Square = Square(39);
+    pub const B5: Square = Square(38);
+    pub const C5: Square = Square(37);
+    pub const D5: Square = Square(36);
+    pub const E5: Square = Square(35);
+    pub const F5: Square = Square(34);
+    pub const G5: Square = Square(33);
+    pub const H5: Square = Square(32);
+    pub const A6: Square = Square(47);
+    pub const B6: Square = Square(46);
+    pub const C6: Square = Square(45);
+    pub const D6: Square = Square(44);
+    pub const E6: Square = Square(43);
+    pub const F6: Square = Square(42);
+    pub const G6: Square = Square(41);
+    pub const H6: Square = Square(40);
+    pub const A7: Square = Square(55);
+    pub const B7: Square = Square(54);
+    pub const C7: Square = Square(53);
+    pub const D7: Square = Square(52);
+    pub const E7: Square = Square(51);
+    pub const F7: Square = Square(50);
+    pub const G7: Square = Square(49);
+    pub const H7: Square = Square(48);
+    pub const A8: Square = Square(63);
+    pub const B8: Square = Square(62);
+    pub const C8: Square = Square(61);
+    pub const D8: Square = Square(60);
+    pub const E8: Square = Square(59);
+    pub const F8: Square = Square(58);
+    pub const G8: Square = Square(57);
+    pub const H8: Square = Square(56);
+
+    pub const fn new(index: u8) -> Option<Square> {
+        if index < 64 {
+            Some(Square(index))
+        } else {
+            None
+        }
+    }
+
+    // Files and ranks count from 0, so a1 is (0, 0) and h8 is (7, 7)
+    pub const fn from_file_rank(file: u8, rank: u8) -> Option<Square> {
+        if file < 8 && rank < 8 {
+            Some(Square(rank * 8 + 7 - file))
+        } else {
+            None
+        }
+    }
+
+    pub const fn index(self) -> u8 {
+        self.0
+    }
+
+    pub const fn file(self) -> u8 {
+        7 - self.0 % 8
+    }
+
+    pub const fn rank(self) -> u8 {
+        self.0 / 8
+    }
+
+    pub const fn bit(self) -> Bitboard {
+        Bitboard(1 << self.0)
+    }
+
+    pub const fn offset(self, file_offset: i8, rank_offset: i8) -> Option<Square> {
+        let file = self.file() as i8 + file_offset;
+        let rank = self.rank() as i8 + rank_offset;
+        if file < 0 || rank < 0 {
+            None
+        } else {
+            Square::from_file_rank(file as u8, rank as u8)
+        }
+    }
+
+    pub fn is_dark(self) -> bool {
+        (self.file() + self.rank()).is_multiple_of(2)
+    }
+}
+
+impl TryFrom<u8> for Square {
+    type Error = ParseSquareError;
+
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        Square::new(index).ok_or(ParseSquareError)
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file = (b'a' + self.file()) as char;
+        let rank = (b'1' + self.rank()) as char;
+        write!(f, "{file}{rank}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseSquareError;
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "not a square, expected a file a-h followed by a rank 1-8"
+        )
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Ok(Square::from_file_rank(file - b'a', rank - b'1').unwrap())
+            }
+            _ => Err(ParseSquareError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algebraic_names() {
+        assert_eq!("e4".parse(), Ok(Square::E4));
+        assert_eq!(Square::E4.to_string(), "e4");
+        assert_eq!((Square::E4.file(), Square::E4.rank()), (4, 3));
+        assert_eq!(Square::H1.index(), 0);
+        assert_eq!(Square::A8.index(), 63);
+        assert!("i1".parse::<Square>().is_err());
+        assert!("e9".parse::<Square>().is_err());
+        assert!("e".parse::<Square>().is_err());
+        assert!(Square::try_from(64).is_err());
+    }
+
+    #[test]
+    fn offsets_stay_on_the_board() {
+        assert_eq!(Square::E4.offset(1, 2), Some(Square::F6));
+        assert_eq!(Square::H4.offset(1, 0), None);
+        assert_eq!(Square::A1.offset(0, -1), None);
+        assert!(Square::A1.is_dark());
+        assert!(!Square::H1.is_dark());
+    }
+}
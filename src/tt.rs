@@ -0,0 +1,198 @@
+// Transposition table: search results by Zobrist hash, so positions reached again through another
+// move order or in the next iteration don't have to be searched from scratch
+use crate::chess::Move;
+use crate::search::MATE_SCORE;
+
+// Mate scores count plies from the root but are stored counting from the position itself, the
+// same position can be reached at different plies
+const MATE_BOUND: i32 = MATE_SCORE - 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower, // The score is at least this, the search failed high
+    Upper, // The score is at most this, no move reached alpha
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableEntry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    hash: u64,
+    age: u8, // Which search stored the entry
+    entry: TableEntry,
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Option<Slot>>,
+    age: u8,
+}
+
+impl TranspositionTable {
+    // A table that takes up about the given number of megabytes
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let count = (megabytes * 1024 * 1024 / std::mem::size_of::<Option<Slot>>()).max(1);
+        TranspositionTable {
+            slots: vec![None; count],
+            age: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.fill(None);
+        self.age = 0;
+    }
+
+    // Called at the start of every search, entries from earlier searches are replaced first
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    // How full the table is in permille, estimated from the first thousand slots like UCI's
+    // hashfull. Entries left over from earlier searches don't count.
+    pub fn hashfull(&self) -> u32 {
+        let sample = &self.slots[..self.slots.len().min(1000)];
+        let used = sample
+            .iter()
+            .filter(|slot| slot.is_some_and(|slot| slot.age == self.age))
+            .count();
+        (used * 1000 / sample.len()) as u32
+    }
+
+    pub fn probe(&self, hash: u64, ply: u32) -> Option<TableEntry> {
+        match self.slots[self.get_index(hash)] {
+            Some(slot) if slot.hash == hash => Some(TableEntry {
+                score: score_from_table(slot.entry.score, ply),
+                ..slot.entry
+            }),
+            _ => None,
+        }
+    }
+
+    // An entry replaces the one in its slot unless that one is from the current search and was
+    // searched deeper. A position that is searched again keeps its best move when the new search
+    // didn't find one.
+    pub fn store(&mut self, hash: u64, ply: u32, mut entry: TableEntry) {
+        let index = self.get_index(hash);
+        if let Some(slot) = self.slots[index] {
+            let same_position = slot.hash == hash;
+            if !same_position && slot.age == self.age && slot.entry.depth > entry.depth {
+                return;
+            }
+            if same_position && entry.best_move.is_none() {
+                entry.best_move = slot.entry.best_move;
+            }
+        }
+        entry.score = score_to_table(entry.score, ply);
+        self.slots[index] = Some(Slot {
+            hash,
+            age: self.age,
+            entry,
+        });
+    }
+
+    fn get_index(&self, hash: u64) -> usize {
+        (hash % self.slots.len() as u64) as usize
+    }
+}
+
+fn score_to_table(score: i32, ply: u32) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: u32) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::Chess;
+
+    fn entry(score: i32, depth: u32) -> TableEntry {
+        TableEntry {
+            best_move: None,
+            score,
+            depth,
+            bound: Bound::Exact,
+        }
+    }
+
+    #[test]
+    fn mate_scores_count_from_the_probing_ply() {
+        let mut table = TranspositionTable::new(1);
+        // Mate in 5 plies from the root, seen 3 plies in is mate in 2 from the position itself
+        table.store(42, 3, entry(MATE_SCORE - 5, 4));
+        assert_eq!(table.probe(42, 1).unwrap().score, MATE_SCORE - 3);
+        table.store(42, 3, entry(-MATE_SCORE + 5, 4));
+        assert_eq!(table.probe(42, 7).unwrap().score, -MATE_SCORE + 9);
+        table.store(42, 3, entry(150, 4));
+        assert_eq!(table.probe(42, 7).unwrap().score, 150);
+        assert_eq!(table.probe(43, 0), None);
+    }
+
+    #[test]
+    fn keeps_deeper_entries_from_the_current_search() {
+        let mut table = TranspositionTable::new(1);
+        let len = table.slots.len() as u64;
+        // Three positions that share a slot
+        let (a, b, c) = (5, 5 + len, 5 + 2 * len);
+        table.store(a, 0, entry(10, 6));
+        table.store(b, 0, entry(20, 2));
+        assert_eq!(table.probe(a, 0).map(|entry| entry.score), Some(10));
+        assert_eq!(table.probe(b, 0), None);
+
+        table.new_search();
+        table.store(c, 0, entry(30, 1));
+        assert_eq!(table.probe(a, 0), None);
+        assert_eq!(table.probe(c, 0).map(|entry| entry.score), Some(30));
+
+        // The same position is always updated, and keeps its move if the new entry has none
+        let mv = Chess::new().generate_legal_moves()[0];
+        table.store(
+            c,
+            0,
+            TableEntry {
+                best_move: Some(mv),
+                ..entry(30, 1)
+            },
+        );
+        table.store(c, 0, entry(40, 0));
+        assert_eq!(table.probe(c, 0).unwrap().best_move, Some(mv));
+        assert_eq!(table.probe(c, 0).unwrap().score, 40);
+    }
+
+    #[test]
+    fn reports_how_full_it_is() {
+        let mut table = TranspositionTable::new(1);
+        assert!(table.slots.len() * std::mem::size_of::<Option<Slot>>() <= 1024 * 1024);
+        assert_eq!(table.hashfull(), 0);
+        for hash in 0..500 {
+            table.store(hash, 0, entry(0, 1));
+        }
+        assert_eq!(table.hashfull(), 500);
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+        table.store(7, 0, entry(0, 1));
+        table.clear();
+        assert_eq!(table.probe(7, 0), None);
+    }
+}
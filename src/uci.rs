@@ -0,0 +1,140 @@
+// Coordinate notation used by UCI engines, e.g. "e2e4", "e1g1" or "e7e8q"
+use crate::chess::{Chess, Move, MoveError, Piece, PieceType, PromotionError, Side};
+use crate::square::Square;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UciError {
+    InvalidSyntax(String),
+    IllegalMove(MoveError),
+}
+
+impl std::fmt::Display for UciError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UciError::InvalidSyntax(uci) => {
+                write!(
+                    f,
+                    "'{uci}' is not a move in UCI notation like e2e4 or e7e8q"
+                )
+            }
+            UciError::IllegalMove(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for UciError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UciError::IllegalMove(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Move {
+    pub fn to_uci(&self) -> String {
+        let mut uci = format!("{}{}", self.from, self.to);
+        if let Some(promotion) = self.promotion {
+            uci.push(Piece::new(Side::Black, promotion).get_char());
+        }
+        uci
+    }
+}
+
+impl Chess {
+    // Finds the legal move written in UCI notation, or says why there isn't one
+    pub fn parse_uci_move(&mut self, uci: &str) -> Result<Move, UciError> {
+        let invalid = || UciError::InvalidSyntax(uci.to_string());
+        if !uci.is_ascii() || !(4..=5).contains(&uci.len()) {
+            return Err(invalid());
+        }
+        let from: Square = uci[0..2].parse().map_err(|_| invalid())?;
+        let to: Square = uci[2..4].parse().map_err(|_| invalid())?;
+        let promotion = match uci[4..].chars().next() {
+            Some(letter) => match Piece::from_char(letter) {
+                Some(piece) if PieceType::PROMOTIONS.contains(&piece.piece_type) => {
+                    Some(piece.piece_type)
+                }
+                Some(_) => {
+                    return Err(UciError::IllegalMove(MoveError::InvalidPromotion(
+                        PromotionError::InvalidPiece,
+                    )))
+                }
+                None => return Err(invalid()),
+            },
+            None => None,
+        };
+
+        self.find_legal_move(from, to, promotion)
+            .map_err(UciError::IllegalMove)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_its_own_uci() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ] {
+            let mut chess = Chess::from_fen(fen).unwrap();
+            for mv in chess.generate_legal_moves() {
+                assert_eq!(chess.parse_uci_move(&mv.to_uci()), Ok(mv));
+            }
+        }
+
+        let mut chess = Chess::from_fen("4k3/1P6/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(chess.parse_uci_move("e1g1").unwrap().to_uci(), "e1g1");
+        assert!(chess.parse_uci_move("e1g1").unwrap().is_castle());
+        assert_eq!(chess.parse_uci_move("b7b8n").unwrap().to_uci(), "b7b8n");
+        assert_eq!(
+            chess.parse_uci_move("b7b8Q").unwrap().promotion,
+            Some(PieceType::Queen)
+        );
+    }
+
+    #[test]
+    fn explains_rejected_moves() {
+        let mut chess = Chess::from_fen("4k3/1P6/8/8/8/8/r7/4K2R w K - 0 1").unwrap();
+        let errors = [
+            ("e2", UciError::InvalidSyntax("e2".to_string())),
+            ("e1e9", UciError::InvalidSyntax("e1e9".to_string())),
+            ("b7b8x", UciError::InvalidSyntax("b7b8x".to_string())),
+            (
+                "e3e4",
+                UciError::IllegalMove(MoveError::NoPiece(Square::E3)),
+            ),
+            (
+                "e8d8",
+                UciError::IllegalMove(MoveError::NotYourTurn(Square::E8)),
+            ),
+            // The king can't step onto the rook's rank and the rook can't jump over the king
+            (
+                "e1f2",
+                UciError::IllegalMove(MoveError::LeavesKingInCheck(Square::E1, Square::F2)),
+            ),
+            (
+                "h1a1",
+                UciError::IllegalMove(MoveError::UnreachableSquare(Square::H1, Square::A1)),
+            ),
+            (
+                "b7b8",
+                UciError::IllegalMove(MoveError::InvalidPromotion(PromotionError::MissingPiece)),
+            ),
+            (
+                "b7b8k",
+                UciError::IllegalMove(MoveError::InvalidPromotion(PromotionError::InvalidPiece)),
+            ),
+            (
+                "e1d1q",
+                UciError::IllegalMove(MoveError::InvalidPromotion(PromotionError::NotAPromotion)),
+            ),
+        ];
+        for (uci, err) in errors {
+            assert_eq!(chess.parse_uci_move(uci), Err(err), "{uci}");
+        }
+    }
+}
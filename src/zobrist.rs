@@ -0,0 +1,61 @@
+// Zobrist keys: 768 piece keys, 4 castling keys, 8 en passant file keys and one key for white to
+// move. The keys are generated here, so hashes will not match those of Polyglot opening books.
+use crate::chess::{Castling, Piece, Side};
+use crate::square::Square;
+
+const CASTLING_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+
+const KEYS: [u64; 781] = generate_keys();
+
+// Fixed seed so hashes are the same on every run and can be stored
+const fn generate_keys() -> [u64; 781] {
+    let mut keys = [0; 781];
+    let mut state: u64 = 0x2545F4914F6CDD1D;
+    let mut i = 0;
+    while i < keys.len() {
+        // splitmix64
+        state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+pub fn piece(piece: Piece, square: Square) -> u64 {
+    let kind = piece.piece_type as usize * 2 + piece.side as usize;
+    KEYS[64 * kind + square.index() as usize]
+}
+
+pub fn castling(rights: u8) -> u64 {
+    let mut key = 0;
+    for (i, right) in [
+        Castling::WHITE_KING_SIDE,
+        Castling::WHITE_QUEEN_SIDE,
+        Castling::BLACK_KING_SIDE,
+        Castling::BLACK_QUEEN_SIDE,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if rights & right != 0 {
+            key ^= KEYS[CASTLING_OFFSET + i];
+        }
+    }
+    key
+}
+
+pub fn en_passant(file: u8) -> u64 {
+    KEYS[EN_PASSANT_OFFSET + file as usize]
+}
+
+pub fn turn(side: Side) -> u64 {
+    match side {
+        Side::White => KEYS[TURN_OFFSET],
+        Side::Black => 0,
+    }
+}
@@ -0,0 +1,42 @@
+// Exercises the library the way a headless caller would, through the crate root only
+use chess::{Chess, GameStatus, PieceType, Side, Square, STARTING_FEN};
+
+#[test]
+fn play_fools_mate() {
+    let mut chess = Chess::new();
+    for (from, to) in [("f2", "f3"), ("e7", "e5"), ("g2", "g4"), ("d8", "h4")] {
+        let from: Square = from.parse().unwrap();
+        let to: Square = to.parse().unwrap();
+        let mv = chess
+            .generate_legal_moves()
+            .into_iter()
+            .find(|mv| mv.from == from && mv.to == to)
+            .unwrap();
+        chess.make_move(mv);
+    }
+    assert_eq!(chess.status(), GameStatus::Checkmate(Side::Black));
+}
+
+#[test]
+fn try_move_reports_the_outcome() {
+    let mut chess = Chess::new();
+    let mut status = GameStatus::Ongoing;
+    for (from, to) in [("f2", "f3"), ("e7", "e5"), ("g2", "g4"), ("d8", "h4")] {
+        let from: Square = from.parse().unwrap();
+        let to: Square = to.parse().unwrap();
+        status = chess.try_move(from, to, None).unwrap().status;
+    }
+    assert_eq!(status, GameStatus::Checkmate(Side::Black));
+    assert!(chess.try_move(Square::E1, Square::E2, None).is_err());
+}
+
+#[test]
+fn fen_round_trip() {
+    let chess = Chess::from_fen(STARTING_FEN).unwrap();
+    assert_eq!(chess.to_fen(), STARTING_FEN);
+    let queen = chess.get_piece_at(Square::D1).unwrap();
+    assert_eq!(
+        (queen.side, queen.piece_type),
+        (Side::White, PieceType::Queen)
+    );
+}